@@ -1,57 +1,245 @@
-use bevy::prelude::*;
+use bevy::{asset::AssetLoader, prelude::*};
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path};
+
 pub struct AnimationPlugin;
 
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum LoopMode {
+    // Starts over after the last frame
+    Loop,
+    // Plays once and goes back to the first frame
+    Once,
+    // Goes back and forth between the first and the last frame
+    PingPong,
+    // Plays once and stays on the last frame
+    HoldLast,
+}
+
+impl Default for LoopMode {
+    fn default() -> Self {
+        LoopMode::Loop
+    }
+}
+
+#[derive(Deserialize)]
 pub struct AnimationFrame {
-    pub index: i32,
+    // Index into the texture atlas
+    pub index: u32,
+    // Seconds the frame is shown
     pub time: f32,
+    // Sent as AnimationMarker every time the frame comes up, e.g "flap"
+    #[serde(default)]
+    pub markers: Vec<String>,
 }
 
-pub struct Animation {
+#[derive(Deserialize)]
+pub struct AnimationClip {
     pub frames: Vec<AnimationFrame>,
-    pub current_frame: i32,
+    #[serde(default)]
+    pub mode: LoopMode,
+}
+
+// The clips of one sprite sheet by name, loaded from .anim files
+#[derive(Deserialize)]
+pub struct AnimationSet {
+    pub clips: HashMap<String, AnimationClip>,
+}
+
+#[derive(Default)]
+pub struct AnimationSetLoader;
+
+impl AssetLoader<AnimationSet> for AnimationSetLoader {
+    fn from_bytes(&self, asset_path: &Path, bytes: Vec<u8>) -> Result<AnimationSet, anyhow::Error> {
+        let animation_set: AnimationSet = ron::de::from_bytes(&bytes)?;
+        for (name, clip) in animation_set.clips.iter() {
+            if clip.frames.is_empty() {
+                anyhow::bail!("{}: clip {} has no frames", asset_path.display(), name);
+            }
+            if clip.frames.iter().any(|frame| frame.time <= 0.0) {
+                anyhow::bail!(
+                    "{}: clip {} has a frame without time",
+                    asset_path.display(),
+                    name
+                );
+            }
+        }
+        Ok(animation_set)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["anim"];
+        EXTENSIONS
+    }
+}
+
+// Sent when a clip that doesn't loop played to the end
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub clip: String,
+}
+
+// Sent when a frame with markers comes up
+pub struct AnimationMarker {
+    pub entity: Entity,
+    pub clip: String,
+    pub marker: String,
 }
 
-pub struct Animations {
-    pub animations: Vec<Animation>,
-    pub current_animation: i32,
+// Plays clips from an AnimationSet on the TextureAtlasSprite of the entity
+pub struct Animator {
+    pub animations: Handle<AnimationSet>,
+    clip: String,
+    frame: usize,
+    elapsed: f32,
+    // Direction for LoopMode::PingPong
+    forward: bool,
+    finished: bool,
+    // Times the clip got back to its first frame or finished
+    cycles: u32,
+    // The markers of the current frame still need to be sent
+    frame_entered: bool,
+}
+
+impl Animator {
+    pub fn new(animations: Handle<AnimationSet>, clip: &str) -> Animator {
+        Animator {
+            animations,
+            clip: clip.to_string(),
+            frame: 0,
+            elapsed: 0.0,
+            forward: true,
+            finished: false,
+            cycles: 0,
+            frame_entered: true,
+        }
+    }
+
+    pub fn clip(&self) -> &str {
+        &self.clip
+    }
+
+    // Starts the clip from its first frame, unless it's already playing
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.restart(clip);
+        }
+    }
+
+    // Starts the clip from its first frame, even if it's already playing
+    pub fn restart(&mut self, clip: &str) {
+        *self = Animator::new(self.animations, clip);
+    }
+
+    // True once a clip that doesn't loop played to the end
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Counts up every time the clip completes, starting at 0 when the clip starts
+    pub fn cycles(&self) -> u32 {
+        self.cycles
+    }
+
+    // The frame after the current one, None when the clip is over
+    fn next_frame(&self, mode: LoopMode, last_frame: usize) -> Option<(usize, bool)> {
+        let frame = self.frame;
+        match mode {
+            LoopMode::Loop if frame == last_frame => Some((0, true)),
+            LoopMode::Once | LoopMode::HoldLast if frame == last_frame => None,
+            LoopMode::Loop | LoopMode::Once | LoopMode::HoldLast => Some((frame + 1, true)),
+            LoopMode::PingPong if last_frame == 0 => Some((0, true)),
+            LoopMode::PingPong => match (self.forward, frame) {
+                (true, frame) if frame == last_frame => Some((frame - 1, false)),
+                (false, 0) => Some((1, true)),
+                (true, frame) => Some((frame + 1, true)),
+                (false, frame) => Some((frame - 1, false)),
+            },
+        }
+    }
+}
+
+// Stops every animation on its current frame while paused
+#[derive(Default)]
+pub struct AnimationSettings {
+    pub paused: bool,
 }
 
 impl Plugin for AnimationPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(animate_system.system());
+        app.init_resource::<AnimationSettings>()
+            .add_asset::<AnimationSet>()
+            .add_event::<AnimationFinished>()
+            .add_event::<AnimationMarker>()
+            .add_asset_loader::<AnimationSet, AnimationSetLoader>()
+            .add_system(animate_system.system());
     }
 }
 
 fn animate_system(
-    mut query: Query<(
-        &mut Timer,
-        &mut TextureAtlasSprite,
-        &mut Animations,
-    )>,
+    animation_settings: Res<AnimationSettings>,
+    time: Res<Time>,
+    animation_sets: Res<Assets<AnimationSet>>,
+    mut finished_events: ResMut<Events<AnimationFinished>>,
+    mut marker_events: ResMut<Events<AnimationMarker>>,
+    mut query: Query<(Entity, &mut Animator, &mut TextureAtlasSprite)>,
 ) {
-    for (mut timer, mut sprite, mut animations) in &mut query.iter() {
-        if timer.finished {
-            let current_animation_index = animations.current_animation;
-            match animations
-                .animations
-                .get_mut(current_animation_index as usize)
-            {
-                Some(animation) => {
-                    animation.current_frame += 1;
-                    if animation.current_frame as usize >= animation.frames.len() {
-                        animation.current_frame = 0;
+    if animation_settings.paused {
+        return;
+    }
+    for (entity, mut animator, mut sprite) in &mut query.iter() {
+        // Nothing to play until the file is loaded
+        let clip = match animation_sets
+            .get(&animator.animations)
+            .and_then(|animation_set| animation_set.clips.get(&animator.clip))
+        {
+            Some(clip) => clip,
+            None => continue,
+        };
+        let last_frame = clip.frames.len() - 1;
+        animator.frame = animator.frame.min(last_frame);
+        if !animator.finished {
+            animator.elapsed += time.delta_seconds;
+        }
+        loop {
+            if animator.frame_entered {
+                animator.frame_entered = false;
+                for marker in clip.frames[animator.frame].markers.iter() {
+                    marker_events.send(AnimationMarker {
+                        entity,
+                        clip: animator.clip.clone(),
+                        marker: marker.clone(),
+                    });
+                }
+            }
+            // A long frame can skip over several animation frames
+            if animator.finished || animator.elapsed < clip.frames[animator.frame].time {
+                break;
+            }
+            animator.elapsed -= clip.frames[animator.frame].time;
+            match animator.next_frame(clip.mode, last_frame) {
+                Some((frame, forward)) => {
+                    if frame == 0 {
+                        animator.cycles += 1;
                     }
-                    let frame_data = animation
-                        .frames
-                        .get(animation.current_frame as usize)
-                        .unwrap();
-                    timer.duration = frame_data.time;
-                    if let Some(frame) = animation.frames.get(animation.current_frame as usize) {
-                        sprite.index = frame.index as u32;
+                    animator.frame = frame;
+                    animator.forward = forward;
+                    animator.frame_entered = true;
+                }
+                None => {
+                    animator.finished = true;
+                    animator.cycles += 1;
+                    animator.elapsed = 0.0;
+                    if clip.mode == LoopMode::Once {
+                        animator.frame = 0;
                     }
+                    finished_events.send(AnimationFinished {
+                        entity,
+                        clip: animator.clip.clone(),
+                    });
                 }
-                None => {}
             }
         }
+        sprite.index = clip.frames[animator.frame].index;
     }
 }
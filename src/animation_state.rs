@@ -0,0 +1,182 @@
+use crate::animation;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use animation::*;
+
+// Parameters that aren't set count as 0 and false
+#[derive(Clone, Debug)]
+pub enum Condition {
+    Above(String, f32),
+    Below(String, f32),
+    Flag(String),
+    NotFlag(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransitionRule {
+    // Switch as soon as the conditions hold
+    Immediate,
+    // Wait for the current clip to complete a cycle, while the conditions keep holding
+    FinishCycle,
+}
+
+struct Transition {
+    // None goes from any state
+    from: Option<String>,
+    to: String,
+    conditions: Vec<Condition>,
+    rule: TransitionRule,
+}
+
+// Picks the clip an Animator plays from named states.
+// Other systems set the parameters, the transitions decide when the state changes
+pub struct AnimationStateMachine {
+    // Clip played in each state
+    states: HashMap<String, String>,
+    transitions: Vec<Transition>,
+    current: String,
+    floats: HashMap<String, f32>,
+    flags: HashMap<String, bool>,
+    // Transition waiting for the clip, with the cycle count when it started waiting
+    pending: Option<(usize, u32)>,
+}
+
+impl AnimationStateMachine {
+    pub fn new(initial_state: &str, clip: &str) -> AnimationStateMachine {
+        let mut states = HashMap::new();
+        states.insert(initial_state.to_string(), clip.to_string());
+        AnimationStateMachine {
+            states,
+            transitions: Vec::new(),
+            current: initial_state.to_string(),
+            floats: HashMap::new(),
+            flags: HashMap::new(),
+            pending: None,
+        }
+    }
+
+    pub fn with_state(mut self, state: &str, clip: &str) -> AnimationStateMachine {
+        self.states.insert(state.to_string(), clip.to_string());
+        self
+    }
+
+    // Transitions are tried in the order they were added
+    pub fn with_transition(
+        mut self,
+        from: &str,
+        to: &str,
+        rule: TransitionRule,
+        conditions: Vec<Condition>,
+    ) -> AnimationStateMachine {
+        self.transitions.push(Transition {
+            from: Some(from.to_string()),
+            to: to.to_string(),
+            conditions,
+            rule,
+        });
+        self
+    }
+
+    pub fn with_transition_from_any(
+        mut self,
+        to: &str,
+        rule: TransitionRule,
+        conditions: Vec<Condition>,
+    ) -> AnimationStateMachine {
+        self.transitions.push(Transition {
+            from: None,
+            to: to.to_string(),
+            conditions,
+            rule,
+        });
+        self
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.floats.insert(name.to_string(), value);
+    }
+
+    pub fn set_flag(&mut self, name: &str, value: bool) {
+        self.flags.insert(name.to_string(), value);
+    }
+
+    fn float(&self, name: &str) -> f32 {
+        self.floats.get(name).copied().unwrap_or(0.0)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.get(name).copied().unwrap_or(false)
+    }
+
+    fn holds(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Above(name, value) => self.float(name) > *value,
+            Condition::Below(name, value) => self.float(name) < *value,
+            Condition::Flag(name) => self.flag(name),
+            Condition::NotFlag(name) => !self.flag(name),
+        }
+    }
+
+    // The first transition out of the current state with all conditions holding
+    fn matching_transition(&self) -> Option<usize> {
+        self.transitions.iter().position(|transition| {
+            let from_current = match &transition.from {
+                Some(from) => *from == self.current,
+                None => true,
+            };
+            from_current
+                && transition.to != self.current
+                && transition
+                    .conditions
+                    .iter()
+                    .all(|condition| self.holds(condition))
+        })
+    }
+
+    // Returns the clip to play when the state changed
+    fn update(&mut self, animator: &Animator) -> Option<&str> {
+        let index = match self.matching_transition() {
+            Some(index) => index,
+            None => {
+                self.pending = None;
+                return None;
+            }
+        };
+        if self.transitions[index].rule == TransitionRule::FinishCycle {
+            let started_cycles = match self.pending {
+                Some((pending, cycles)) if pending == index => cycles,
+                _ => animator.cycles(),
+            };
+            // A clip that finished won't complete another cycle
+            if animator.cycles() == started_cycles && !animator.is_finished() {
+                self.pending = Some((index, started_cycles));
+                return None;
+            }
+        }
+        self.pending = None;
+        self.current = self.transitions[index].to.clone();
+        self.states.get(&self.current).map(|clip| clip.as_str())
+    }
+}
+
+pub struct AnimationStatePlugin;
+
+impl Plugin for AnimationStatePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(animation_state_system.system());
+    }
+}
+
+fn animation_state_system(
+    mut state_machine: Mut<AnimationStateMachine>,
+    mut animator: Mut<Animator>,
+) {
+    if let Some(clip) = state_machine.update(&animator) {
+        animator.play(clip);
+    }
+}
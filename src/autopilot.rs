@@ -0,0 +1,62 @@
+use crate::bird;
+use crate::collision;
+use crate::gamestate;
+use crate::physics;
+use crate::pipes;
+use bevy::prelude::*;
+
+use bird::*;
+use collision::*;
+use gamestate::*;
+use physics::*;
+use pipes::*;
+
+// Plays the game by asking for jumps, the same way the keyboard does.
+// Always flies the bird in the menu, and plays runs with PlayerControl::Autopilot
+pub struct AutopilotPlugin;
+
+impl Plugin for AutopilotPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_fixed_system_to_stage(FIXED_PRE_UPDATE, autopilot_system.system());
+    }
+}
+
+fn autopilot_system(
+    state: Res<GameStateMachine>,
+    player_control: Res<PlayerControl>,
+    jump_height: Res<JumpHeight>,
+    gravity: Res<Gravity>,
+    mut jump_input: ResMut<JumpInput>,
+    mut player_query: Query<(&Player, &Translation, &Velocity)>,
+    mut pipe_query: Query<(&Pipe, &Translation, &Collider)>,
+    mut collider_query: Query<(&Collider, &Translation)>,
+) {
+    let in_control = match state.current() {
+        GameState::Menu => true,
+        GameState::Playing => *player_control == PlayerControl::Autopilot,
+        GameState::Loading | GameState::Paused | GameState::Dead => false,
+    };
+    if !in_control {
+        return;
+    }
+    let mut pipes = Vec::new();
+    for (_pipe, translation, collider) in &mut pipe_query.iter() {
+        pipes.push((translation.0, collider.shape.size()));
+    }
+    let mut score_colliders = Vec::new();
+    for (collider, translation) in &mut collider_query.iter() {
+        if collider.kind == ColliderKind::ScoreGiver {
+            score_colliders.push(translation.0);
+        }
+    }
+    // How far above the jump start the bird gets
+    let jump_apex = jump_height.0 * jump_height.0 / (2.0 * gravity.0);
+    for (_player, translation, velocity) in &mut player_query.iter() {
+        let target_y =
+            next_gap(translation.0.x(), &pipes, &score_colliders).map_or(0.0, |gap| gap.center_y);
+        // Jump when falling below the target, so the arc is centered on it
+        if velocity.0.y() <= 0.0 && translation.0.y() < target_y - jump_apex * 0.5 {
+            jump_input.requested = true;
+        }
+    }
+}
@@ -1,26 +1,59 @@
 use bevy::{
     input::{keyboard::KeyCode, Input},
     prelude::*,
-    sprite::collide_aabb::{collide},
 };
 
 use crate::animation;
+use crate::animation_state;
+use crate::collision;
 use crate::gamedata;
 use crate::gamestate;
+use crate::loading;
 use crate::physics;
 use crate::pipes;
-use crate::screens;
+use crate::playfield;
+use crate::pool;
 
 use animation::*;
+use animation_state::*;
+use collision::*;
 use gamedata::*;
 use gamestate::*;
+use loading::*;
 use physics::*;
 use pipes::*;
-use screens::*;
+use playfield::*;
+use pool::*;
+
+// Width and height of one frame in the bird texture
+pub const BIRD_SIZE: f32 = 32.0;
+// The hitbox is smaller than the bird, to feel more fair
+const HITBOX_SCALE: f32 = 0.4;
 
 pub struct Player;
 pub struct JumpHeight(pub f32);
 
+// Who decides when the bird jumps
+#[derive(PartialEq)]
+pub enum PlayerControl {
+    Keyboard,
+    Replay,
+    Autopilot,
+    // Jumps are requested from outside the app, like the training environment
+    External,
+}
+
+// A jump asked for by the controller, used up on the next fixed tick
+#[derive(Default)]
+pub struct JumpInput {
+    pub requested: bool,
+}
+
+// Sent every time the bird jumps during a run
+pub struct PlayerJumped {
+    pub tick: u64,
+}
+
 // data for animating rotation
 pub struct VelocityRotator {
     pub angle_up: f32,
@@ -33,169 +66,153 @@ pub struct BirdPlugin;
 
 impl Plugin for BirdPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(player_input.system())
-            .add_system(player_bounds_system.system())
-            .add_system(player_collision_system.system())
+        app.add_event::<PlayerJumped>()
+            .init_resource::<JumpInput>()
+            .add_system(keyboard_control_system.system())
+            .add_fixed_system_to_stage(FIXED_PHYSICS, player_input.system())
+            .add_fixed_system(player_ceiling_system.system())
+            .add_fixed_system(player_death_system.system())
+            .add_fixed_system(player_score_system.system())
             .add_system(velocity_rotator_system.system())
-            .add_system(velocity_animator_system.system());
+            .add_system(bird_animation_parameters_system.system())
+            .on_exit(GameState::Loading, spawn_bird_system.system());
+    }
+}
+
+fn keyboard_control_system(
+    state: Res<GameStateMachine>,
+    player_control: Res<PlayerControl>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut jump_input: ResMut<JumpInput>,
+) {
+    // The press that starts or unpauses a run isn't a jump
+    if *player_control != PlayerControl::Keyboard || state.current() != GameState::Playing {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        jump_input.requested = true;
     }
 }
 
 fn player_input(
-    game_data: Res<GameData>,
+    state: Res<GameStateMachine>,
     jump_height: Res<JumpHeight>,
-    keyboard_input: Res<Input<KeyCode>>,
+    fixed_timestep: Res<FixedTimestep>,
+    mut jump_input: ResMut<JumpInput>,
+    mut jumped_events: ResMut<Events<PlayerJumped>>,
     _player: Mut<Player>,
-    translation: Mut<Translation>,
     velocity: Mut<Velocity>,
 ) {
-    match game_data.game_state {
+    match state.current() {
+        // The autopilot keeps the bird flying in the menu
         GameState::Menu => {
-            handle_stay_in_screen(jump_height, velocity, translation);
+            handle_jump(&mut jump_input, jump_height, velocity);
         }
         GameState::Playing => {
-            handle_jump(keyboard_input, jump_height, velocity);
+            if handle_jump(&mut jump_input, jump_height, velocity) {
+                jumped_events.send(PlayerJumped {
+                    tick: fixed_timestep.tick,
+                });
+            }
         }
-        GameState::Dead => {}
+        GameState::Loading | GameState::Paused | GameState::Dead => {}
     }
 }
 
-// Auto jump until input is given
-fn handle_stay_in_screen(
+// Returns true if the bird jumped
+fn handle_jump(
+    jump_input: &mut ResMut<JumpInput>,
     jump_height: Res<JumpHeight>,
     mut velocity: Mut<Velocity>,
-    translation: Mut<Translation>,
-) {
-    if translation.0.y() < 0.0 {
+) -> bool {
+    if jump_input.requested {
+        jump_input.requested = false;
         velocity.0.set_y(jump_height.0);
+        return true;
     }
+    false
 }
 
-fn handle_jump(
-    keyboard_input: Res<Input<KeyCode>>,
-    jump_height: Res<JumpHeight>,
-    mut velocity: Mut<Velocity>,
+fn player_ceiling_system(
+    mut hit_ceiling_event_reader: Local<EventReader<PlayerHitCeiling>>,
+    hit_ceiling_events: Res<Events<PlayerHitCeiling>>,
+    playfield: Res<Playfield>,
+    mut player_query: Query<(&Player, &mut Translation, &mut Velocity)>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        velocity.0.set_y(jump_height.0);
+    if hit_ceiling_event_reader.iter(&hit_ceiling_events).count() == 0 {
+        return;
+    }
+    for (_p, mut translation, mut velocity) in &mut player_query.iter() {
+        bounce_against_ceiling(&playfield, &mut translation, &mut velocity);
     }
 }
 
-fn player_bounds_system(
-    mut commands: Commands,
-    mut game_data: ResMut<GameData>,
-    mut player_query: Query<(&Player, &mut Translation, &mut Velocity)>,
-    mut pipe_query: Query<(&Pipe, &Translation, &Collider, &Sprite, Entity)>,
-    mut score_collider_query: Query<(&Translation, &Collider, Entity)>,
-    mut end_screen_query: Query<(&EndScreen, &mut Draw)>,
+// Where the top of the bird stops
+pub fn ceiling_height(playfield: &Playfield) -> f32 {
+    playfield.half_height() - BIRD_SIZE * SPRITE_SCALE
+}
+
+pub fn bounce_against_ceiling(
+    playfield: &Playfield,
+    translation: &mut Translation,
+    velocity: &mut Velocity,
 ) {
-    let half_screen_size = 1280.0 * 0.5;
-    let player_size = 32.0 * 6.0;
-    for (_p, mut translation, mut velocity) in &mut player_query.iter() {
-        // bounce against ceiling
-        if translation.0.y() > half_screen_size - player_size {
-            velocity.0.set_y(-3.0);
-            translation.0.set_y(half_screen_size - player_size);
-        }
-        // death on bottom touch
-        if translation.0.y() < -half_screen_size {
-            trigger_death(
-                &mut commands,
-                &mut game_data,
-                &mut pipe_query,
-                &mut score_collider_query,
-                &mut end_screen_query,
-            );
-        }
+    let ceiling = ceiling_height(playfield);
+    if translation.0.y() > ceiling {
+        velocity.0.set_y(-3.0);
+        translation.0.set_y(ceiling);
     }
 }
 
-fn player_collision_system(
-    mut commands: Commands,
+// Touching a pipe or the ground ends the run
+fn player_death_system(
+    mut hit_pipe_event_reader: Local<EventReader<PlayerHitPipe>>,
+    mut hit_ground_event_reader: Local<EventReader<PlayerHitGround>>,
+    hit_pipe_events: Res<Events<PlayerHitPipe>>,
+    hit_ground_events: Res<Events<PlayerHitGround>>,
+    mut state: ResMut<GameStateMachine>,
+    game_data: Res<GameData>,
+    fixed_timestep: Res<FixedTimestep>,
+    mut run_events: ResMut<Events<RunEvent>>,
+) {
+    // Read both, so old hits don't show up later
+    let hit_pipe = hit_pipe_event_reader.iter(&hit_pipe_events).count() > 0;
+    let hit_ground = hit_ground_event_reader.iter(&hit_ground_events).count() > 0;
+    if (hit_pipe || hit_ground) && state.is_settled_in(GameState::Playing) {
+        trigger_death(&mut state, &game_data, &fixed_timestep, &mut run_events);
+    }
+}
+
+fn player_score_system(
+    state: Res<GameStateMachine>,
+    mut pool: ResMut<EntityPool>,
+    mut passed_gap_event_reader: Local<EventReader<PlayerPassedGap>>,
+    passed_gap_events: Res<Events<PlayerPassedGap>>,
     mut game_data: ResMut<GameData>,
-    mut worlds: Query<&mut World>,
-    mut player_query: Query<(&Player, &Translation)>,
-    mut pipe_query: Query<(&Pipe, &Translation, &Collider, &Sprite, Entity)>,
-    mut score_collider_query: Query<(&Translation, &Collider, Entity)>,
-    mut end_screen_query: Query<(&EndScreen, &mut Draw)>,
 ) {
-    // Player size can't be fetched from AtlasTextureSprite, so I'm hard coding it here...
-    let mut player_size = 6.0 * 32.0;
-    // Make player hitbox half size, to feel more fair
-    player_size *= 0.4;
-    let player_size_vec = (player_size, player_size);
-    for (_player, player_translation) in &mut player_query.iter() {
-        for (translation, collider, entity) in &mut score_collider_query.iter() {
-            if *collider != Collider::ScoreGiver {
-                continue;
-            }
-            let collision = collide(
-                player_translation.0,
-                player_size_vec.into(),
-                translation.0,
-                Vec2::new(10.0, 1280.0),
-            );
-            if collision.is_some() {
-                game_data.score += 1;
-                println!("got score!: {}", game_data.score);
-                // Remove coin collider, quick simple solution
-                for world in &mut worlds.iter() {
-                    if !world.contains(entity) {
-                        commands.despawn(entity);
-                    }
-                }
-            }
-        }
-        // Check for collision
-        let mut did_collide = false;
-        for (_pipe, pipe_translation, _collider, pipe_sprite, _pipe_entity) in
-            &mut pipe_query.iter()
-        {
-            let collision = collide(
-                player_translation.0,
-                player_size_vec.into(),
-                pipe_translation.0,
-                pipe_sprite.size * 6.0,
-            );
-            if collision.is_some() {
-                did_collide = true;
-                break;
-            }
-        }
-        if did_collide {
-            trigger_death(
-                &mut commands,
-                &mut game_data,
-                &mut pipe_query,
-                &mut score_collider_query,
-                &mut end_screen_query,
-            );
+    for passed_gap in passed_gap_event_reader.iter(&passed_gap_events) {
+        // A gap passed on the tick of the death doesn't count, the run already ended with its score
+        if state.is_settled_in(GameState::Playing) {
+            game_data.score += 1;
+            println!("got score!: {}", game_data.score);
         }
+        // Park the score zone, so it can't be passed again
+        pool.release(passed_gap.score_zone);
     }
 }
 
+// Cleaning up pipes and showing the end screen happens when entering GameState::Dead
 fn trigger_death(
-    commands: &mut Commands,
-    game_data: &mut ResMut<GameData>,
-    pipe_query: &mut Query<(&Pipe, &Translation, &Collider, &Sprite, Entity)>,
-    score_query: &mut Query<(&Translation, &Collider, Entity)>,
-    end_screen_query: &mut Query<(&EndScreen, &mut Draw)>,
+    state: &mut ResMut<GameStateMachine>,
+    game_data: &GameData,
+    fixed_timestep: &Res<FixedTimestep>,
+    run_events: &mut ResMut<Events<RunEvent>>,
 ) {
-    game_data.game_state = GameState::Dead;
-    game_data.score = 0;
-    // Despawn all pipes
-    for (_p, _pt, _c, _ps, pipe_entity) in &mut pipe_query.iter() {
-        commands.despawn(pipe_entity);
-    }
-    // Despawn score colliders
-    for (_t, collider, score_entity) in &mut score_query.iter() {
-        if *collider == Collider::ScoreGiver {
-            commands.despawn(score_entity);
-        }
-    }
-    for (_es, mut draw) in &mut end_screen_query.iter() {
-        draw.is_visible = true;
-    }
+    state.set(GameState::Dead);
+    run_events.send(RunEvent::Ended {
+        score: game_data.score,
+        tick: fixed_timestep.tick,
+    });
 }
 
 fn velocity_rotator_system(
@@ -217,34 +234,34 @@ fn velocity_rotator_system(
     rotation.0 = Quat::from_rotation_z(rad_angle);
 }
 
-fn velocity_animator_system(mut query: Query<(&mut Animations, &Velocity)>) {
-    for (mut animations, velocity) in &mut query.iter() {
-        if velocity.0.y() > 0.0 {
-            animations.current_animation = 0;
-        } else {
-            animations.current_animation = 1;
-        }
+// Feeds the animation state machine of the player and the ghost
+fn bird_animation_parameters_system(
+    state: Res<GameStateMachine>,
+    mut query: Query<(&mut AnimationStateMachine, &Velocity, Option<&Player>)>,
+) {
+    for (mut animation_states, velocity, player) in &mut query.iter() {
+        animation_states.set_float("velocity_y", velocity.0.y());
+        animation_states.set_flag(
+            "dead",
+            player.is_some() && state.current() == GameState::Dead,
+        );
     }
 }
 
-pub fn spawn_bird(
-    commands: &mut Commands,
-    asset_server: &mut Res<AssetServer>,
-    mut textures: &mut ResMut<Assets<Texture>>,
-    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+// The collider is sized after the texture, so this waits for the assets
+fn spawn_bird_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    textures: Res<Assets<Texture>>,
 ) {
-    let texture_handle = asset_server
-        .load_sync(&mut textures, "assets/bird.png")
-        .unwrap();
-
-    let texture = textures.get(&texture_handle).unwrap();
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, texture.size, 2, 2);
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    let texture = textures.get(&game_assets.bird_texture).unwrap();
+    // One frame of the 2x2 sheet
+    let frame_size = texture.size * 0.5 * SPRITE_SCALE;
 
     commands
         .spawn(SpriteSheetComponents {
-            texture_atlas: texture_atlas_handle,
-            scale: Scale(6.0),
+            texture_atlas: game_assets.bird_atlas,
+            scale: Scale(SPRITE_SCALE),
             translation: Translation::new(0.0, 0.0, 100.0),
             draw: Draw {
                 is_transparent: true,
@@ -253,46 +270,54 @@ pub fn spawn_bird(
             },
             ..Default::default()
         })
-        .with(Timer::from_seconds(0.1, true))
         .with(Player)
+        .with(Collider::new(
+            ColliderKind::Player,
+            Shape::Circle {
+                radius: frame_size.x() * HITBOX_SCALE * 0.5,
+            },
+        ))
         .with(AffectedByGravity)
-        .with(VelocityRotator {
-            angle_up: std::f32::consts::PI * 0.5 * 0.7,
-            angle_down: -std::f32::consts::PI * 0.5 * 0.5,
-            velocity_max: 400.0,
-        })
+        .with(bird_velocity_rotator())
         .with(Velocity(Vec2::zero()))
-        .with(Animations {
-            animations: vec![
-                Animation {
-                    current_frame: 0,
-                    frames: vec![
-                        AnimationFrame {
-                            index: 0,
-                            time: 0.1,
-                        },
-                        AnimationFrame {
-                            index: 1,
-                            time: 0.1,
-                        },
-                        AnimationFrame {
-                            index: 2,
-                            time: 0.3,
-                        },
-                        AnimationFrame {
-                            index: 1,
-                            time: 0.1,
-                        },
-                    ],
-                },
-                Animation {
-                    current_frame: 0,
-                    frames: vec![AnimationFrame {
-                        index: 3,
-                        time: 0.2,
-                    }],
-                },
-            ],
-            current_animation: 0,
-        });
+        .with(Animator::new(game_assets.bird_animations, "flap"))
+        .with(bird_animation_states());
+}
+
+pub fn bird_animation_states() -> AnimationStateMachine {
+    AnimationStateMachine::new("flap", "flap")
+        .with_state("fall", "fall")
+        .with_state("dead", "fall")
+        .with_transition_from_any(
+            "dead",
+            TransitionRule::Immediate,
+            vec![Condition::Flag("dead".to_string())],
+        )
+        .with_transition(
+            "dead",
+            "fall",
+            TransitionRule::Immediate,
+            vec![Condition::NotFlag("dead".to_string())],
+        )
+        // Flap right away on a jump, but let the wings finish before falling
+        .with_transition(
+            "fall",
+            "flap",
+            TransitionRule::Immediate,
+            vec![Condition::Above("velocity_y".to_string(), 0.0)],
+        )
+        .with_transition(
+            "flap",
+            "fall",
+            TransitionRule::FinishCycle,
+            vec![Condition::Below("velocity_y".to_string(), 0.0)],
+        )
+}
+
+pub fn bird_velocity_rotator() -> VelocityRotator {
+    VelocityRotator {
+        angle_up: std::f32::consts::PI * 0.5 * 0.7,
+        angle_down: -std::f32::consts::PI * 0.5 * 0.5,
+        velocity_max: 400.0,
+    }
 }
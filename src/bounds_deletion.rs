@@ -1,30 +1,162 @@
-use bevy::prelude::*;
+use crate::physics;
+use crate::playfield;
+use crate::pool;
+use bevy::{app::stage, prelude::*};
+use physics::*;
+use playfield::*;
+use pool::*;
 
-pub struct OffsceenDeletion;
+// How far past the edges of the playfield things are removed, on top of their own size
+pub const OFFSCREEN_PADDING: f32 = 300.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edges {
+    pub left: bool,
+    pub right: bool,
+    pub top: bool,
+    pub bottom: bool,
+}
+
+impl Edges {
+    // Everything scrolling in from the right
+    pub const LEFT: Edges = Edges {
+        left: true,
+        right: false,
+        top: false,
+        bottom: false,
+    };
+    pub const ALL: Edges = Edges {
+        left: true,
+        right: true,
+        top: true,
+        bottom: true,
+    };
+}
+
+// Removed once fully past one of the edges, or given back to its pool
+pub struct BoundsDeletion {
+    pub edges: Edges,
+    pub padding: f32,
+}
+
+impl BoundsDeletion {
+    pub fn new(edges: Edges) -> BoundsDeletion {
+        BoundsDeletion {
+            edges,
+            padding: OFFSCREEN_PADDING,
+        }
+    }
+
+    pub fn with_padding(mut self, padding: f32) -> BoundsDeletion {
+        self.padding = padding;
+        self
+    }
+}
+
+// Removed after this many seconds of game time, wherever it is
+pub struct Lifetime {
+    pub seconds: f32,
+    age: f32,
+}
+
+impl Lifetime {
+    pub fn new(seconds: f32) -> Lifetime {
+        Lifetime { seconds, age: 0.0 }
+    }
+}
+
+// Despawns at the end of the frame. Queuing an entity twice,
+// or one that is already gone, does nothing instead of panicking
+#[derive(Default)]
+pub struct DespawnQueue {
+    entities: Vec<Entity>,
+}
+
+impl DespawnQueue {
+    pub fn despawn(&mut self, entity: Entity) {
+        self.entities.push(entity);
+    }
+}
 
 pub struct BoundsDeletionPlugin;
 
 impl Plugin for BoundsDeletionPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(offscreen_remove_system.system());
+        app.init_resource::<DespawnQueue>()
+            .add_system(bounds_deletion_system.system())
+            .add_fixed_system(lifetime_system.system())
+            .add_system_to_stage(stage::LAST, despawn_queue_system.thread_local_system());
+    }
+}
+
+// Pooled entities go back to the pool, everything else is despawned
+fn remove(
+    entity: Entity,
+    pooled: Option<&Pooled>,
+    pool: &mut EntityPool,
+    despawn_queue: &mut DespawnQueue,
+) {
+    match pooled {
+        Some(pooled) if pooled.is_active() => pool.release(entity),
+        // Already waiting in the pool
+        Some(_) => {}
+        None => despawn_queue.despawn(entity),
     }
 }
 
-fn offscreen_remove_system(
-    mut commands: Commands,
-    mut worlds: Query<&mut World>,
-    mut pipe_query: Query<(Entity, &mut Translation, &OffsceenDeletion)>,
+fn bounds_deletion_system(
+    playfield: Res<Playfield>,
+    mut pool: ResMut<EntityPool>,
+    mut despawn_queue: ResMut<DespawnQueue>,
+    mut query: Query<(
+        Entity,
+        &Translation,
+        &BoundsDeletion,
+        Option<&Sprite>,
+        Option<&Scale>,
+        Option<&Pooled>,
+    )>,
 ) {
-    let padding = 300.0;
-    for (entity, translation, _od) in &mut pipe_query.iter() {
-        // Left side of screen
-        if translation.0.x() < -1920.0 * 0.5 - padding {
-            for world in &mut worlds.iter() {
-                // Due to despawning of entity from other systems, avoid despawn panic
-                if !world.contains(entity) {
-                    commands.despawn(entity);
-                }
-            }
+    for (entity, translation, bounds_deletion, sprite, scale, pooled) in &mut query.iter() {
+        // Wide sprites like mountains are only removed once all of it is past
+        let half_size = match (sprite, scale) {
+            (Some(sprite), Some(scale)) => sprite.size * scale.0 * 0.5,
+            (Some(sprite), None) => sprite.size * 0.5,
+            (None, _) => Vec2::zero(),
+        };
+        let limit = Vec2::new(playfield.half_width(), playfield.half_height())
+            + half_size
+            + Vec2::new(bounds_deletion.padding, bounds_deletion.padding);
+        let edges = bounds_deletion.edges;
+        let (x, y) = (translation.0.x(), translation.0.y());
+        if (edges.left && x < -limit.x())
+            || (edges.right && x > limit.x())
+            || (edges.top && y > limit.y())
+            || (edges.bottom && y < -limit.y())
+        {
+            remove(entity, pooled, &mut pool, &mut despawn_queue);
         }
     }
 }
+
+fn lifetime_system(
+    fixed_timestep: Res<FixedTimestep>,
+    mut pool: ResMut<EntityPool>,
+    mut despawn_queue: ResMut<DespawnQueue>,
+    mut query: Query<(Entity, &mut Lifetime, Option<&Pooled>)>,
+) {
+    for (entity, mut lifetime, pooled) in &mut query.iter() {
+        lifetime.age += fixed_timestep.step;
+        if lifetime.age >= lifetime.seconds {
+            remove(entity, pooled, &mut pool, &mut despawn_queue);
+        }
+    }
+}
+
+fn despawn_queue_system(world: &mut World, resources: &mut Resources) {
+    let mut despawn_queue = resources.get_mut::<DespawnQueue>().unwrap();
+    for entity in despawn_queue.entities.drain(..) {
+        // Fails for entities queued twice or removed some other way, which is fine
+        let _ = world.despawn(entity);
+    }
+}
@@ -0,0 +1,332 @@
+use crate::bird;
+use crate::physics;
+use crate::playfield;
+use bevy::prelude::*;
+
+use bird::*;
+use physics::*;
+use playfield::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColliderKind {
+    // The bird
+    Player,
+    // Kills the player
+    Solid,
+    // Gives a point when the player passes through
+    ScoreGiver,
+}
+
+// Shapes don't rotate with the entity, which is why the bird uses a circle
+#[derive(Clone, Copy, Debug)]
+pub enum Shape {
+    Aabb { half_size: Vec2 },
+    Circle { radius: f32 },
+    // Upright, half_height goes from the center to the middle of each round end
+    Capsule { half_height: f32, radius: f32 },
+}
+
+impl Shape {
+    // Every shape is a box with rounded corners, some with a zero sized box or zero radius
+    fn half_size(&self) -> Vec2 {
+        match *self {
+            Shape::Aabb { half_size } => half_size,
+            Shape::Circle { .. } => Vec2::zero(),
+            Shape::Capsule { half_height, .. } => Vec2::new(0.0, half_height),
+        }
+    }
+
+    fn radius(&self) -> f32 {
+        match *self {
+            Shape::Aabb { .. } => 0.0,
+            Shape::Circle { radius } => radius,
+            Shape::Capsule { radius, .. } => radius,
+        }
+    }
+
+    // Width and height of the area the shape covers
+    pub fn size(&self) -> Vec2 {
+        (self.half_size() + Vec2::new(self.radius(), self.radius())) * 2.0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Collider {
+    pub kind: ColliderKind,
+    pub shape: Shape,
+    // From the entity translation to the center of the shape
+    pub offset: Vec2,
+}
+
+impl Collider {
+    pub fn new(kind: ColliderKind, shape: Shape) -> Collider {
+        Collider {
+            kind,
+            shape,
+            offset: Vec2::zero(),
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec2) -> Collider {
+        self.offset = offset;
+        self
+    }
+
+    pub fn center(&self, translation: Vec3) -> Vec2 {
+        Vec2::new(translation.x(), translation.y()) + self.offset
+    }
+
+    pub fn overlaps(&self, translation: Vec3, other: &Collider, other_translation: Vec3) -> bool {
+        let distance = self.center(translation) - other.center(other_translation);
+        let half_size = self.shape.half_size() + other.shape.half_size();
+        // How far apart the boxes are on each axis, 0 when they overlap on it
+        let gap_x = (distance.x().abs() - half_size.x()).max(0.0);
+        let gap_y = (distance.y().abs() - half_size.y()).max(0.0);
+        let radius = self.shape.radius() + other.shape.radius();
+        gap_x * gap_x + gap_y * gap_y <= radius * radius
+    }
+
+    // Earliest point of a move, 0 -> 1, where the two touch. None if they never do.
+    // Checks the whole way between the positions, so fast movers can't pass through each other
+    pub fn sweep(
+        &self,
+        from: Vec3,
+        to: Vec3,
+        other: &Collider,
+        other_from: Vec3,
+        other_to: Vec3,
+    ) -> Option<f32> {
+        // Move relative to the other collider, it stays at the origin
+        let start = self.center(from) - other.center(other_from);
+        let delta = self.center(to) - other.center(other_to) - start;
+        let half_size = self.shape.half_size() + other.shape.half_size();
+        let radius = self.shape.radius() + other.shape.radius();
+        // Both rounded boxes together are a box with rounded corners again:
+        // a wide box, a tall box, and a circle on each corner
+        let mut hits = vec![
+            segment_box(start, delta, half_size + Vec2::new(radius, 0.0)),
+            segment_box(start, delta, half_size + Vec2::new(0.0, radius)),
+        ];
+        if radius > 0.0 {
+            for corner in [
+                Vec2::new(half_size.x(), half_size.y()),
+                Vec2::new(-half_size.x(), half_size.y()),
+                Vec2::new(half_size.x(), -half_size.y()),
+                Vec2::new(-half_size.x(), -half_size.y()),
+            ]
+            .iter()
+            {
+                hits.push(segment_circle(start, delta, *corner, radius));
+            }
+        }
+        hits.into_iter()
+            .flatten()
+            .fold(None, |earliest: Option<f32>, t| {
+                Some(earliest.map_or(t, |earliest| earliest.min(t)))
+            })
+    }
+}
+
+// Where a segment from start along delta first enters a box centered on the origin
+fn segment_box(start: Vec2, delta: Vec2, half_size: Vec2) -> Option<f32> {
+    let mut t_enter = 0.0f32;
+    let mut t_exit = 1.0f32;
+    for &(start, delta, half_size) in [
+        (start.x(), delta.x(), half_size.x()),
+        (start.y(), delta.y(), half_size.y()),
+    ]
+    .iter()
+    {
+        // Not moving on this axis, it has to be inside already
+        if delta.abs() < std::f32::EPSILON {
+            if start.abs() > half_size {
+                return None;
+            }
+            continue;
+        }
+        let t0 = (-half_size - start) / delta;
+        let t1 = (half_size - start) / delta;
+        t_enter = t_enter.max(t0.min(t1));
+        t_exit = t_exit.min(t0.max(t1));
+        if t_enter > t_exit {
+            return None;
+        }
+    }
+    Some(t_enter)
+}
+
+// Where a segment from start along delta first enters a circle
+fn segment_circle(start: Vec2, delta: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let from_center = start - center;
+    let c = from_center.length_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let a = delta.length_squared();
+    if a < std::f32::EPSILON {
+        return None;
+    }
+    let b = from_center.dot(delta);
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / a;
+    match t >= 0.0 && t <= 1.0 {
+        true => Some(t),
+        false => None,
+    }
+}
+
+// Sent from the fixed update, tick is the fixed tick it happened on
+pub struct PlayerHitPipe {
+    pub pipe: Entity,
+    pub tick: u64,
+}
+
+pub struct PlayerPassedGap {
+    pub score_zone: Entity,
+    pub tick: u64,
+}
+
+pub struct PlayerHitGround {
+    pub tick: u64,
+}
+
+pub struct PlayerHitCeiling {
+    pub tick: u64,
+}
+
+// Finds what the player touches and sends events for it, other systems decide what happens
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<PlayerHitPipe>()
+            .add_event::<PlayerPassedGap>()
+            .add_event::<PlayerHitGround>()
+            .add_event::<PlayerHitCeiling>()
+            .add_fixed_system_to_stage(FIXED_COLLISION, player_collision_system.system());
+    }
+}
+
+fn player_collision_system(
+    fixed_timestep: Res<FixedTimestep>,
+    playfield: Res<Playfield>,
+    mut hit_pipe_events: ResMut<Events<PlayerHitPipe>>,
+    mut passed_gap_events: ResMut<Events<PlayerPassedGap>>,
+    mut hit_ground_events: ResMut<Events<PlayerHitGround>>,
+    mut hit_ceiling_events: ResMut<Events<PlayerHitCeiling>>,
+    mut player_query: Query<(&Player, &Translation, &Collider, Option<&Interpolated>)>,
+    mut collider_query: Query<(&Translation, &Collider, Entity, Option<&Interpolated>)>,
+) {
+    let tick = fixed_timestep.tick;
+    for (_player, player_translation, player_collider, player_interpolated) in
+        &mut player_query.iter()
+    {
+        // Test the whole move of this tick, not only where it ended
+        let player_from = previous_translation(player_translation, player_interpolated);
+        let y = player_translation.0.y();
+        if y < -playfield.half_height() {
+            hit_ground_events.send(PlayerHitGround { tick });
+        }
+        if y > ceiling_height(&playfield) {
+            hit_ceiling_events.send(PlayerHitCeiling { tick });
+        }
+        for (translation, collider, entity, interpolated) in &mut collider_query.iter() {
+            let hit = player_collider.sweep(
+                player_from,
+                player_translation.0,
+                collider,
+                previous_translation(translation, interpolated),
+                translation.0,
+            );
+            if hit.is_none() {
+                continue;
+            }
+            match collider.kind {
+                ColliderKind::Solid => hit_pipe_events.send(PlayerHitPipe { pipe: entity, tick }),
+                ColliderKind::ScoreGiver => passed_gap_events.send(PlayerPassedGap {
+                    score_zone: entity,
+                    tick,
+                }),
+                ColliderKind::Player => {}
+            }
+        }
+    }
+}
+
+// Where it was at the start of this tick. Just spawned things haven't moved yet
+fn previous_translation(translation: &Translation, interpolated: Option<&Interpolated>) -> Vec3 {
+    interpolated.map_or(translation.0, |interpolated| interpolated.previous())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pipe() -> Collider {
+        Collider::new(
+            ColliderKind::Solid,
+            Shape::Aabb {
+                half_size: Vec2::new(5.0, 100.0),
+            },
+        )
+    }
+
+    fn bird() -> Collider {
+        Collider::new(ColliderKind::Player, Shape::Circle { radius: 10.0 })
+    }
+
+    fn corner_box() -> Collider {
+        Collider::new(
+            ColliderKind::Solid,
+            Shape::Aabb {
+                half_size: Vec2::new(10.0, 10.0),
+            },
+        )
+    }
+
+    #[test]
+    fn sweep_catches_passing_through_in_one_step() {
+        let (from, to) = (Vec3::new(-100.0, 0.0, 0.0), Vec3::new(100.0, 0.0, 0.0));
+        let at = Vec3::zero();
+        assert!(!bird().overlaps(from, &pipe(), at));
+        assert!(!bird().overlaps(to, &pipe(), at));
+        let t = bird().sweep(from, to, &pipe(), at, at).unwrap();
+        // Touches once the center is a radius away from the left side
+        assert!((t - 85.0 / 200.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn sweep_follows_the_rounded_corners() {
+        let at = Vec3::zero();
+        // Passes the corner at (10, 10) diagonally, just further away than the radius
+        let miss = bird().sweep(
+            Vec3::new(8.0, 28.0, 0.0),
+            Vec3::new(28.0, 8.0, 0.0),
+            &corner_box(),
+            at,
+            at,
+        );
+        assert_eq!(miss, None);
+        // Same line a bit closer, cuts the corner
+        let hit = bird().sweep(
+            Vec3::new(6.0, 26.0, 0.0),
+            Vec3::new(26.0, 6.0, 0.0),
+            &corner_box(),
+            at,
+            at,
+        );
+        assert!(hit.is_some());
+    }
+
+    #[test]
+    fn sweep_starts_at_zero_when_already_overlapping() {
+        let at = Vec3::zero();
+        let from = Vec3::new(5.0, 0.0, 0.0);
+        assert!(bird().overlaps(from, &pipe(), at));
+        let t = bird().sweep(from, Vec3::new(50.0, 0.0, 0.0), &pipe(), at, at);
+        assert_eq!(t, Some(0.0));
+    }
+}
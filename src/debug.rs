@@ -0,0 +1,265 @@
+use crate::bounds_deletion;
+use crate::collision;
+use crate::loading;
+use crate::physics;
+use crate::pipes;
+use crate::playfield;
+use crate::pool;
+use bevy::prelude::*;
+
+use bounds_deletion::*;
+use collision::*;
+use loading::*;
+use physics::*;
+use pipes::*;
+use playfield::*;
+use pool::*;
+
+// Above the letterbox bars, so lines outside the playfield show too
+const DEBUG_Z: f32 = 700.0;
+const LINE_THICKNESS: f32 = 4.0;
+const CIRCLE_SEGMENTS: usize = 16;
+// Velocity arrows are this long per unit of velocity
+const VELOCITY_SCALE: f32 = 0.2;
+
+// Toggled with F3
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+}
+
+// Everything drawn by the overlay, redrawn every frame
+struct DebugShape;
+struct DebugText;
+
+struct DebugMaterials {
+    collider: Handle<ColorMaterial>,
+    player: Handle<ColorMaterial>,
+    velocity: Handle<ColorMaterial>,
+    marker: Handle<ColorMaterial>,
+}
+
+impl FromResources for DebugMaterials {
+    fn from_resources(resources: &Resources) -> Self {
+        let mut materials = resources.get_mut::<Assets<ColorMaterial>>().unwrap();
+        DebugMaterials {
+            collider: materials.add(Color::rgb(0.0, 1.0, 0.0).into()),
+            player: materials.add(Color::rgb(1.0, 0.0, 1.0).into()),
+            velocity: materials.add(Color::rgb(1.0, 1.0, 0.0).into()),
+            marker: materials.add(Color::rgb(1.0, 0.0, 0.0).into()),
+        }
+    }
+}
+
+// Draws colliders, velocities and spawn and despawn lines with plain sprites.
+// Only for the windowed game
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<DebugOverlay>()
+            .init_resource::<DebugMaterials>()
+            .add_startup_system(setup.system())
+            .add_system(toggle_debug_system.system())
+            // Spawned lines need the transform systems in POST_UPDATE to run after them,
+            // or they are drawn at the origin. Shows the positions from before this frame's ticks
+            .add_system(debug_draw_system.system())
+            .add_system(debug_text_system.system());
+    }
+}
+
+fn setup(mut commands: Commands, game_assets: Res<GameAssets>) {
+    let font_handle = game_assets.font;
+    commands
+        .spawn(TextComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font: font_handle,
+                style: TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            },
+            draw: Draw {
+                is_transparent: true,
+                is_visible: false,
+                render_commands: Vec::new(),
+            },
+            ..Default::default()
+        })
+        .with(DebugText);
+}
+
+fn toggle_debug_system(keyboard_input: Res<Input<KeyCode>>, mut overlay: ResMut<DebugOverlay>) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+// A thin sprite from one point to another
+fn spawn_line(commands: &mut Commands, material: Handle<ColorMaterial>, from: Vec2, to: Vec2) {
+    let delta = to - from;
+    let center = (from + to) * 0.5;
+    commands
+        .spawn(SpriteComponents {
+            material,
+            sprite: Sprite {
+                size: Vec2::new(delta.length(), LINE_THICKNESS),
+            },
+            translation: Translation::new(center.x(), center.y(), DEBUG_Z),
+            rotation: Rotation::from_rotation_z(delta.y().atan2(delta.x())),
+            ..Default::default()
+        })
+        .with(DebugShape);
+}
+
+fn spawn_box(commands: &mut Commands, material: Handle<ColorMaterial>, center: Vec2, half: Vec2) {
+    let corners = [
+        center + Vec2::new(-half.x(), -half.y()),
+        center + Vec2::new(half.x(), -half.y()),
+        center + Vec2::new(half.x(), half.y()),
+        center + Vec2::new(-half.x(), half.y()),
+    ];
+    for i in 0..corners.len() {
+        spawn_line(
+            commands,
+            material,
+            corners[i],
+            corners[(i + 1) % corners.len()],
+        );
+    }
+}
+
+// Outline of the rounded box a shape is made of, the round parts drawn with segments
+fn spawn_rounded_box(
+    commands: &mut Commands,
+    material: Handle<ColorMaterial>,
+    center: Vec2,
+    half: Vec2,
+    radius: f32,
+) {
+    if radius <= 0.0 {
+        spawn_box(commands, material, center, half);
+        return;
+    }
+    let mut points = Vec::with_capacity(CIRCLE_SEGMENTS + 4);
+    // A quarter circle on each corner, going around counter clockwise
+    let corners = [
+        Vec2::new(half.x(), half.y()),
+        Vec2::new(-half.x(), half.y()),
+        Vec2::new(-half.x(), -half.y()),
+        Vec2::new(half.x(), -half.y()),
+    ];
+    let steps = CIRCLE_SEGMENTS / 4;
+    for (quarter, corner) in corners.iter().enumerate() {
+        for step in 0..=steps {
+            let angle = (quarter as f32 + step as f32 / steps as f32) * std::f32::consts::PI * 0.5;
+            points.push(center + *corner + Vec2::new(angle.cos(), angle.sin()) * radius);
+        }
+    }
+    for i in 0..points.len() {
+        spawn_line(
+            commands,
+            material,
+            points[i],
+            points[(i + 1) % points.len()],
+        );
+    }
+}
+
+fn debug_draw_system(
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    materials: Res<DebugMaterials>,
+    playfield: Res<Playfield>,
+    mut shape_query: Query<(&DebugShape, Entity)>,
+    mut collider_query: Query<(&Collider, &Translation)>,
+    mut velocity_query: Query<(&Velocity, &Translation)>,
+) {
+    for (_shape, entity) in &mut shape_query.iter() {
+        commands.despawn(entity);
+    }
+    if !overlay.enabled {
+        return;
+    }
+    let mut pipe_height = None;
+    for (collider, translation) in &mut collider_query.iter() {
+        let material = match collider.kind {
+            ColliderKind::Player => materials.player,
+            ColliderKind::Solid | ColliderKind::ScoreGiver => materials.collider,
+        };
+        if collider.kind == ColliderKind::Solid {
+            pipe_height = Some(collider.shape.size().y());
+        }
+        let center = collider.center(translation.0);
+        match collider.shape {
+            Shape::Aabb { half_size } => spawn_box(&mut commands, material, center, half_size),
+            Shape::Circle { radius } => {
+                spawn_rounded_box(&mut commands, material, center, Vec2::zero(), radius)
+            }
+            Shape::Capsule {
+                half_height,
+                radius,
+            } => spawn_rounded_box(
+                &mut commands,
+                material,
+                center,
+                Vec2::new(0.0, half_height),
+                radius,
+            ),
+        }
+    }
+    for (velocity, translation) in &mut velocity_query.iter() {
+        let from = Vec2::new(translation.0.x(), translation.0.y());
+        let to = from + velocity.0 * VELOCITY_SCALE;
+        if from != to {
+            spawn_line(&mut commands, materials.velocity, from, to);
+        }
+    }
+    // Only the padding, each sprite is removed once its far edge is this far past the playfield
+    let despawn_x = -playfield.half_width() - OFFSCREEN_PADDING;
+    spawn_line(
+        &mut commands,
+        materials.marker,
+        Vec2::new(despawn_x, -playfield.half_height()),
+        Vec2::new(despawn_x, playfield.half_height()),
+    );
+    // Gap centers stay between these, the pipe size is only known once one spawned
+    if let Some(pipe_height) = pipe_height {
+        let limit = gap_center_limit(&playfield, pipe_height);
+        for y in [limit, -limit].iter() {
+            spawn_line(
+                &mut commands,
+                materials.marker,
+                Vec2::new(-playfield.half_width(), *y),
+                Vec2::new(playfield.half_width(), *y),
+            );
+        }
+    }
+}
+
+fn debug_text_system(
+    overlay: Res<DebugOverlay>,
+    pool: Res<EntityPool>,
+    mut text_query: Query<(&DebugText, &mut Text, &mut Draw)>,
+    mut entity_query: Query<Without<DebugShape, Entity>>,
+) {
+    // Not counting the overlay itself
+    let mut entity_count = 0;
+    for _entity in &mut entity_query.iter() {
+        entity_count += 1;
+    }
+    for (_debug_text, mut text, mut draw) in &mut text_query.iter() {
+        draw.is_visible = overlay.enabled;
+        text.value = format!("entities: {} ({} pooled)", entity_count, pool.free_count());
+    }
+}
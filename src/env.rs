@@ -0,0 +1,184 @@
+use crate::bird;
+use crate::game;
+use crate::gamestate;
+use crate::headless;
+
+use bird::*;
+use game::*;
+use gamestate::*;
+use headless::*;
+
+// Simulated seconds per step, one fixed tick
+const STEP_SECONDS: f32 = 1.0 / 60.0;
+// Small reward for every tick survived, so staying alive is worth something before the first pipe
+const ALIVE_REWARD: f32 = 0.01;
+const DEATH_REWARD: f32 = -1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Idle,
+    Jump,
+}
+
+// What the agent sees after every step.
+// Without a gap ahead yet, the gap is assumed in the middle of the screen right at the bird
+#[derive(Clone, Copy, Debug)]
+pub struct Observation {
+    pub bird_y: f32,
+    pub bird_velocity_y: f32,
+    // From the bird to the next gap
+    pub gap_distance_x: f32,
+    pub gap_distance_y: f32,
+    pub gap_height: f32,
+}
+
+// Reinforcement learning environment on top of the headless game.
+// Every step simulates one fixed tick
+pub struct FlappyEnv {
+    game: HeadlessGame,
+    score: i32,
+    // The run ended, nothing happens until the next reset
+    done: bool,
+}
+
+impl FlappyEnv {
+    pub fn new() -> FlappyEnv {
+        let mut game = HeadlessGame::new(
+            STEP_SECONDS,
+            GamePlugin {
+                seed: 0,
+                replay: None,
+                autopilot: false,
+            },
+        );
+        game.set_player_control(PlayerControl::External);
+        FlappyEnv {
+            game,
+            score: 0,
+            done: false,
+        }
+    }
+
+    // Starts a new run, the same seed always gives the same pipes
+    // Works at any point, a run that is still going is given up
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game.request_jump(false);
+        if let GameState::Playing | GameState::Paused = self.game.state() {
+            self.game.abandon_run();
+            self.game.step(1);
+        }
+        // Only now, so nothing left of the old run draws from the new seed
+        self.game.reseed(seed);
+        // Runs start on their own from the menu and after a death
+        self.game.step(1);
+        self.score = 0;
+        self.done = false;
+        self.observe()
+    }
+
+    // Returns the observation, the reward for this step, and whether the run is over.
+    // Once it is over, steps keep returning the last observation with no reward until reset.
+    // The game would start the next run on its own otherwise
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool) {
+        if self.done {
+            return (self.observe(), 0.0, true);
+        }
+        self.game.request_jump(action == Action::Jump);
+        self.game.step(1);
+        let done = self.game.state() == GameState::Dead;
+        let score = self.game.game_data().score;
+        let mut reward = (score - self.score) as f32 + ALIVE_REWARD;
+        if done {
+            reward += DEATH_REWARD;
+        }
+        self.score = score;
+        self.done = done;
+        (self.observe(), reward, done)
+    }
+
+    fn observe(&self) -> Observation {
+        let translation = self.game.player_translation().unwrap_or_default();
+        let velocity = self.game.player_velocity().unwrap_or_default();
+        let (gap_distance_x, gap_distance_y, gap_height) = match self.game.next_gap() {
+            Some(gap) => (
+                gap.x - translation.x(),
+                gap.center_y - translation.y(),
+                gap.height,
+            ),
+            None => (0.0, -translation.y(), 0.0),
+        };
+        Observation {
+            bird_y: translation.y(),
+            bird_velocity_y: velocity.y(),
+            gap_distance_x,
+            gap_distance_y,
+            gap_height,
+        }
+    }
+}
+
+impl Default for FlappyEnv {
+    fn default() -> Self {
+        FlappyEnv::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(env: &mut FlappyEnv, steps: u32) -> Vec<(f32, f32, f32)> {
+        (0..steps)
+            .map(|step| {
+                let action = match step % 20 {
+                    0 => Action::Jump,
+                    _ => Action::Idle,
+                };
+                let (observation, _reward, _done) = env.step(action);
+                (
+                    observation.bird_y,
+                    observation.gap_distance_x,
+                    observation.gap_distance_y,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn steps_after_the_end_change_nothing_until_reset() {
+        let mut env = FlappyEnv::new();
+        env.reset(5);
+        // Falls to the ground without jumping
+        let mut done = false;
+        for _ in 0..600 {
+            done = env.step(Action::Idle).2;
+            if done {
+                break;
+            }
+        }
+        assert!(done);
+        for _ in 0..3 {
+            let (_observation, reward, done) = env.step(Action::Jump);
+            assert_eq!(reward, 0.0);
+            assert!(done);
+            assert_eq!(env.game.state(), GameState::Dead);
+        }
+        env.reset(5);
+        assert!(!env.step(Action::Idle).2);
+    }
+
+    #[test]
+    fn reset_mid_run_starts_the_same_run_as_a_fresh_reset() {
+        let mut fresh = FlappyEnv::new();
+        fresh.reset(3);
+        let expected = play(&mut fresh, 300);
+
+        let mut env = FlappyEnv::new();
+        env.reset(1);
+        play(&mut env, 30);
+        assert_eq!(env.game.state(), GameState::Playing);
+        env.reset(3);
+        assert_eq!(env.game.game_data().score, 0);
+        assert_eq!(play(&mut env, 300), expected);
+    }
+}
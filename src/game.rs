@@ -0,0 +1,96 @@
+use crate::animation;
+use crate::animation_state;
+use crate::autopilot;
+use crate::bird;
+use crate::bounds_deletion;
+use crate::collision;
+use crate::gamedata;
+use crate::gamestate;
+use crate::ghost;
+use crate::loading;
+use crate::parallax;
+use crate::physics;
+use crate::pipes;
+use crate::playfield;
+use crate::pool;
+use crate::replay;
+use crate::rng;
+use crate::screens;
+use crate::tuning;
+use crate::tween;
+use bevy::prelude::*;
+
+use animation::*;
+use animation_state::*;
+use autopilot::*;
+use bird::*;
+use bounds_deletion::*;
+use collision::*;
+use gamedata::*;
+use gamestate::*;
+use ghost::*;
+use loading::*;
+use parallax::*;
+use physics::*;
+use pipes::*;
+use playfield::*;
+use pool::*;
+use replay::*;
+use rng::*;
+use screens::*;
+use tuning::*;
+use tween::*;
+
+// Everything that makes up the game, without any window or render plugins.
+// Shared by the windowed build and the headless simulation
+pub struct GamePlugin {
+    // Seed for every random spawner
+    pub seed: u64,
+    // Play this back instead of reading the keyboard
+    pub replay: Option<Replay>,
+    // Let the autopilot play instead of the keyboard
+    pub autopilot: bool,
+}
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // These make the stages the other plugins add systems to. The state stages go
+        // right after UPDATE, in front of the fixed update stages, so physics comes first
+        app.add_plugin(PhysicsPlugin)
+            .add_plugin(GameStatePlugin)
+            .add_plugin(TuningPlugin)
+            .add_plugin(PipePlugin)
+            .add_plugin(BirdPlugin)
+            // Before the loading plugin, which loads the layer textures
+            .add_plugin(ParallaxPlugin)
+            .add_plugin(AnimationPlugin)
+            .add_plugin(AnimationStatePlugin)
+            // After the animation plugin, which loads .anim files
+            .add_plugin(LoadingPlugin)
+            .add_plugin(TweenPlugin)
+            .add_plugin(PoolPlugin)
+            .add_plugin(BoundsDeletionPlugin)
+            .add_plugin(CollisionPlugin)
+            .add_plugin(ScreensPlugin)
+            .add_plugin(ReplayPlugin {
+                playback: self.replay.clone(),
+            })
+            .add_plugin(GhostPlugin)
+            .add_plugin(AutopilotPlugin)
+            .add_startup_system(setup.system())
+            .add_resource(GameRng::from_seed(self.seed))
+            .add_resource(match (&self.replay, self.autopilot) {
+                (Some(_), _) => PlayerControl::Replay,
+                (None, true) => PlayerControl::Autopilot,
+                (None, false) => PlayerControl::Keyboard,
+            })
+            .add_resource(GameData { score: 0 })
+            .init_resource::<Playfield>();
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn(Camera2dComponents::default())
+        .with(PlayfieldCamera);
+}
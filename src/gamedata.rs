@@ -1,7 +1,3 @@
-use crate::gamestate;
-use gamestate::GameState;
-
 pub struct GameData {
-    pub game_state: GameState,
     pub score: i32,
 }
@@ -1,57 +1,277 @@
+use crate::animation;
 use crate::bird;
 use crate::gamedata;
 use crate::physics;
-use crate::screens;
-use bevy::prelude::*;
+use bevy::{
+    app::stage,
+    ecs::{Resources, Schedule, System, World},
+    prelude::*,
+};
+use std::collections::HashMap;
 
+use animation::*;
 use bird::*;
 use gamedata::*;
 use physics::*;
-use screens::*;
 
-#[derive(std::cmp::PartialEq)]
+// Requested state changes are applied here, running the exit and enter hooks
+pub const STATE_TRANSITION: &str = "state_transition";
+// Systems resetting things for a new run go here, it runs before the first fixed tick of the run
+pub const RUN_START: &str = "run_start";
+
+const HOOK: &str = "hook";
+// A hook may request another state, but don't loop forever
+const MAX_TRANSITIONS: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum GameState {
+    // Waiting for the assets, the fixed update doesn't run yet
+    Loading,
     Menu,
     Playing,
+    Paused,
     Dead,
 }
 
+pub struct GameStateMachine {
+    current: GameState,
+    next: Option<GameState>,
+}
+
+impl GameStateMachine {
+    pub fn new(initial: GameState) -> GameStateMachine {
+        GameStateMachine {
+            current: initial,
+            next: None,
+        }
+    }
+
+    pub fn current(&self) -> GameState {
+        self.current
+    }
+
+    // The change happens at the next transition point, not right away
+    pub fn set(&mut self, next: GameState) {
+        self.next = Some(next);
+    }
+
+    // False when about to leave the state, so a death isn't handled twice
+    pub fn is_settled_in(&self, state: GameState) -> bool {
+        self.current == state && self.next.is_none()
+    }
+}
+
+// Sent after the exit and enter hooks ran
+pub struct StateTransition {
+    pub from: GameState,
+    pub to: GameState,
+}
+
+pub enum RunEvent {
+    Started,
+    // tick is the fixed tick the player died on
+    Ended { score: i32, tick: u64 },
+}
+
+struct Hooks {
+    enter: HashMap<GameState, Schedule>,
+    exit: HashMap<GameState, Schedule>,
+}
+
+impl Hooks {
+    fn run(
+        schedules: &mut HashMap<GameState, Schedule>,
+        state: GameState,
+        world: &mut World,
+        resources: &mut Resources,
+    ) {
+        if let Some(schedule) = schedules.get_mut(&state) {
+            schedule.initialize(resources);
+            schedule.run(world, resources);
+        }
+    }
+}
+
+// Systems run once when entering or leaving a state.
+// Taken out while running, like the fixed schedule
+pub struct StateHooks(Option<Hooks>);
+
+pub trait GameStateAppExt {
+    fn on_enter(&mut self, state: GameState, system: Box<dyn System>) -> &mut Self;
+    fn on_exit(&mut self, state: GameState, system: Box<dyn System>) -> &mut Self;
+}
+
+impl GameStateAppExt for AppBuilder {
+    fn on_enter(&mut self, state: GameState, system: Box<dyn System>) -> &mut Self {
+        add_hook(self.resources_mut(), state, system, true);
+        self
+    }
+
+    fn on_exit(&mut self, state: GameState, system: Box<dyn System>) -> &mut Self {
+        add_hook(self.resources_mut(), state, system, false);
+        self
+    }
+}
+
+fn add_hook(resources: &mut Resources, state: GameState, system: Box<dyn System>, enter: bool) {
+    if !resources.contains::<StateHooks>() {
+        resources.insert(StateHooks(Some(Hooks {
+            enter: HashMap::new(),
+            exit: HashMap::new(),
+        })));
+    }
+    let mut state_hooks = resources.get_mut::<StateHooks>().unwrap();
+    let hooks = state_hooks.0.as_mut().unwrap();
+    let schedules = match enter {
+        true => &mut hooks.enter,
+        false => &mut hooks.exit,
+    };
+    schedules
+        .entry(state)
+        .or_insert_with(|| {
+            let mut schedule = Schedule::default();
+            schedule.add_stage(HOOK);
+            schedule
+        })
+        .add_system_to_stage(HOOK, system);
+}
+
 pub struct GameStatePlugin;
 
 impl Plugin for GameStatePlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(handle_gamestate_system.system());
+        app.add_resource(GameStateMachine::new(GameState::Loading))
+            .add_event::<RunEvent>()
+            .add_event::<StateTransition>()
+            .add_stage_after(stage::UPDATE, RUN_START)
+            .add_stage_after(stage::UPDATE, STATE_TRANSITION)
+            .add_system(handle_gamestate_system.system())
+            .add_system_to_stage(
+                STATE_TRANSITION,
+                state_transition_system.thread_local_system(),
+            )
+            // Deaths happen in the fixed update, apply them before the next tick
+            .add_fixed_system_to_stage(
+                FIXED_POST_UPDATE,
+                state_transition_system.thread_local_system(),
+            )
+            .on_enter(GameState::Paused, pause_system.system())
+            .on_exit(GameState::Paused, resume_system.system());
+    }
+}
+
+fn state_transition_system(world: &mut World, resources: &mut Resources) {
+    for _ in 0..MAX_TRANSITIONS {
+        let transition = {
+            let mut state_machine = resources.get_mut::<GameStateMachine>().unwrap();
+            match state_machine.next.take() {
+                Some(next) if next != state_machine.current => {
+                    let from = state_machine.current;
+                    state_machine.current = next;
+                    StateTransition { from, to: next }
+                }
+                _ => return,
+            }
+        };
+        let hooks = match resources.get_mut::<StateHooks>() {
+            Some(mut state_hooks) => state_hooks.0.take(),
+            None => None,
+        };
+        if let Some(mut hooks) = hooks {
+            Hooks::run(&mut hooks.exit, transition.from, world, resources);
+            Hooks::run(&mut hooks.enter, transition.to, world, resources);
+            resources.get_mut::<StateHooks>().unwrap().0 = Some(hooks);
+        }
+        resources
+            .get_mut::<Events<StateTransition>>()
+            .unwrap()
+            .send(transition);
     }
 }
 
 fn handle_gamestate_system(
+    mut state: ResMut<GameStateMachine>,
     mut game_data: ResMut<GameData>,
+    mut run_events: ResMut<Events<RunEvent>>,
     keyboard_input: Res<Input<KeyCode>>,
+    player_control: Res<PlayerControl>,
     mut player_query: Query<(&Player, &mut Translation, &mut Velocity)>,
-    mut end_screen_query: Query<(&EndScreen, &mut Draw)>,
-    mut start_screen_query: Query<(&StartScreen, &mut Draw)>,
 ) {
-    match game_data.game_state {
+    let pause_pressed =
+        keyboard_input.just_pressed(KeyCode::Escape) || keyboard_input.just_pressed(KeyCode::P);
+    match state.current() {
+        // The loading system moves on to the menu
+        GameState::Loading => {}
         GameState::Menu => {
-            if keyboard_input.just_pressed(KeyCode::Space) {
-                game_data.game_state = GameState::Playing;
-                for (_ss, mut draw) in &mut start_screen_query.iter() {
-                    draw.is_visible = false;
-                }
+            // Replays and the autopilot start on their own
+            if keyboard_input.just_pressed(KeyCode::Space)
+                || *player_control != PlayerControl::Keyboard
+            {
+                start_run(
+                    &mut state,
+                    &mut game_data,
+                    &mut run_events,
+                    &mut player_query,
+                );
+            }
+        }
+        GameState::Playing => {
+            if pause_pressed {
+                state.set(GameState::Paused);
+            }
+        }
+        GameState::Paused => {
+            if pause_pressed {
+                state.set(GameState::Playing);
             }
         }
-        GameState::Playing => {}
         GameState::Dead => {
-            if keyboard_input.just_pressed(KeyCode::Space) {
-                game_data.game_state = GameState::Playing;
-                for (_p, mut translation, mut velocity) in &mut player_query.iter() {
-                    translation.0 = Vec3::new(0.0, 0.0, 100.0);
-                    velocity.0.set_y(0.0);
-                }
-                for (_es, mut draw) in &mut end_screen_query.iter() {
-                    draw.is_visible = false;
-                }
+            // The autopilot keeps playing run after run, the environment resets right away
+            if keyboard_input.just_pressed(KeyCode::Space)
+                || *player_control == PlayerControl::Autopilot
+                || *player_control == PlayerControl::External
+            {
+                start_run(
+                    &mut state,
+                    &mut game_data,
+                    &mut run_events,
+                    &mut player_query,
+                );
             }
         }
     }
 }
+
+// Every run starts from the same spot, so replays line up
+fn start_run(
+    state: &mut ResMut<GameStateMachine>,
+    game_data: &mut ResMut<GameData>,
+    run_events: &mut ResMut<Events<RunEvent>>,
+    player_query: &mut Query<(&Player, &mut Translation, &mut Velocity)>,
+) {
+    state.set(GameState::Playing);
+    // The score of the last run stays around for the end screen until here
+    game_data.score = 0;
+    for (_p, mut translation, mut velocity) in &mut player_query.iter() {
+        translation.0 = Vec3::new(0.0, 0.0, 100.0);
+        velocity.0.set_y(0.0);
+    }
+    run_events.send(RunEvent::Started);
+}
+
+// Freezes animations, physics, spawners and everything else in the fixed update
+fn pause_system(
+    mut fixed_timestep: ResMut<FixedTimestep>,
+    mut animation_settings: ResMut<AnimationSettings>,
+) {
+    fixed_timestep.paused = true;
+    animation_settings.paused = true;
+}
+
+fn resume_system(
+    mut fixed_timestep: ResMut<FixedTimestep>,
+    mut animation_settings: ResMut<AnimationSettings>,
+) {
+    fixed_timestep.paused = false;
+    animation_settings.paused = false;
+}
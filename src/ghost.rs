@@ -0,0 +1,117 @@
+use crate::animation;
+use crate::bird;
+use crate::bounds_deletion;
+use crate::gamestate;
+use crate::physics;
+use crate::playfield;
+use crate::replay;
+use bevy::prelude::*;
+
+use animation::*;
+use bird::*;
+use bounds_deletion::*;
+use gamestate::*;
+use physics::*;
+use playfield::*;
+use replay::*;
+
+// A see-through bird flying the personal best run, it can't collide with anything
+pub struct Ghost {
+    jumps: Vec<u64>,
+    next_jump: usize,
+    start_tick: u64,
+}
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_to_stage(RUN_START, ghost_run_system.system())
+            .add_fixed_system_to_stage(FIXED_PHYSICS, ghost_jump_system.system())
+            .add_fixed_system(ghost_bounds_system.system());
+    }
+}
+
+// Start a ghost with every run, and remove it when the run is over
+fn ghost_run_system(
+    mut commands: Commands,
+    mut run_event_reader: Local<EventReader<RunEvent>>,
+    run_events: Res<Events<RunEvent>>,
+    fixed_timestep: Res<FixedTimestep>,
+    replay_state: Res<ReplayState>,
+    mut despawn_queue: ResMut<DespawnQueue>,
+    mut ghost_query: Query<(&Ghost, Entity)>,
+    mut player_query: Query<(&Player, &Handle<TextureAtlas>, &Animator)>,
+) {
+    for run_event in run_event_reader.iter(&run_events) {
+        for (_ghost, entity) in &mut ghost_query.iter() {
+            despawn_queue.despawn(entity);
+        }
+        if let RunEvent::Ended { .. } = run_event {
+            continue;
+        }
+        let best = match replay_state.best() {
+            Some(best) => best,
+            None => continue,
+        };
+        // Same look as the player
+        for (_player, texture_atlas, animator) in &mut player_query.iter() {
+            commands
+                .spawn(SpriteSheetComponents {
+                    texture_atlas: *texture_atlas,
+                    sprite: TextureAtlasSprite {
+                        index: 0,
+                        color: Color::rgba(1.0, 1.0, 1.0, 0.4),
+                    },
+                    scale: Scale(SPRITE_SCALE),
+                    translation: Translation::new(0.0, 0.0, 99.0),
+                    draw: Draw {
+                        is_transparent: true,
+                        is_visible: true,
+                        render_commands: Vec::new(),
+                    },
+                    ..Default::default()
+                })
+                .with(AffectedByGravity)
+                .with(bird_velocity_rotator())
+                .with(Velocity(Vec2::zero()))
+                .with(Animator::new(animator.animations, "flap"))
+                .with(bird_animation_states())
+                .with(Ghost {
+                    jumps: best.jumps.clone(),
+                    next_jump: 0,
+                    start_tick: fixed_timestep.tick,
+                })
+                // Gone on the tick the best run died, the extra half tick absorbs rounding
+                .with(Lifetime::new(
+                    (best.death_tick as f32 + 0.5) * fixed_timestep.step,
+                ))
+                // With other tuning than the best run had, it can fly off anywhere
+                .with(BoundsDeletion::new(Edges::ALL).with_padding(0.0));
+        }
+    }
+}
+
+// Jump on the same ticks as the best run. Like the player's jump it lands after that tick's
+// gravity and movement, the physics plugin adds its systems to FIXED_PHYSICS first
+fn ghost_jump_system(
+    fixed_timestep: Res<FixedTimestep>,
+    jump_height: Res<JumpHeight>,
+    mut ghost: Mut<Ghost>,
+    mut velocity: Mut<Velocity>,
+) {
+    let run_tick = fixed_timestep.tick - ghost.start_tick;
+    if ghost.jumps.get(ghost.next_jump) == Some(&run_tick) {
+        velocity.0.set_y(jump_height.0);
+        ghost.next_jump += 1;
+    }
+}
+
+fn ghost_bounds_system(
+    playfield: Res<Playfield>,
+    _ghost: &Ghost,
+    mut translation: Mut<Translation>,
+    mut velocity: Mut<Velocity>,
+) {
+    bounce_against_ceiling(&playfield, &mut translation, &mut velocity);
+}
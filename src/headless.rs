@@ -0,0 +1,329 @@
+use crate::bird;
+use crate::collision;
+use crate::game;
+use crate::gamedata;
+use crate::gamestate;
+use crate::physics;
+use crate::pipes;
+use crate::pool;
+use crate::replay;
+use crate::rng;
+use bevy::{
+    app::stage,
+    asset::{AddAsset, AssetPlugin},
+    core::CorePlugin,
+    ecs::ResourceRef,
+    input::{
+        keyboard::{ElementState, KeyboardInput},
+        InputPlugin,
+    },
+    prelude::*,
+    render::texture::ImageTextureLoader,
+    text::{Font, FontLoader},
+    transform::TransformPlugin,
+    type_registry::TypeRegistryPlugin,
+};
+use std::time::Duration;
+
+use bird::*;
+use collision::*;
+use game::*;
+use gamedata::*;
+use gamestate::*;
+use physics::*;
+use pipes::*;
+use pool::*;
+use replay::*;
+use rng::*;
+
+pub const HEADLESS_TIME: &str = "headless_time";
+
+// Length of one simulated frame
+pub struct HeadlessTick(pub f32);
+
+// The minimal set of engine plugins the game needs, without window, render or audio
+pub struct HeadlessPlugin {
+    pub tick_seconds: f32,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_plugin(TypeRegistryPlugin::default())
+            .add_plugin(CorePlugin::default())
+            .add_plugin(TransformPlugin::default())
+            .add_plugin(InputPlugin::default())
+            .add_plugin(AssetPlugin::default())
+            // Normally registered by the render, sprite and text plugins
+            .add_asset::<Texture>()
+            .add_asset::<ColorMaterial>()
+            .add_asset::<TextureAtlas>()
+            .add_asset::<Font>()
+            .add_asset_loader::<Texture, ImageTextureLoader>()
+            .add_asset_loader::<Font, FontLoader>()
+            .add_resource(HeadlessTick(self.tick_seconds))
+            .add_stage_after(stage::FIRST, HEADLESS_TIME)
+            .add_system_to_stage(HEADLESS_TIME, fixed_time_system.system())
+            .add_system(sprite_size_system.system());
+    }
+}
+
+// Overwrite the wall clock time with a fixed tick, so runs don't depend on how fast the machine is
+fn fixed_time_system(tick: Res<HeadlessTick>, mut time: ResMut<Time>) {
+    time.delta = Duration::from_secs_f32(tick.0);
+    time.delta_seconds = tick.0;
+    time.delta_seconds_f64 = tick.0 as f64;
+    time.seconds_since_startup += tick.0 as f64;
+}
+
+// The sprite plugin usually sizes sprites after their texture, colliders rely on it
+fn sprite_size_system(
+    materials: Res<Assets<ColorMaterial>>,
+    textures: Res<Assets<Texture>>,
+    mut sprite: Mut<Sprite>,
+    material_handle: &Handle<ColorMaterial>,
+) {
+    let material = match materials.get(material_handle) {
+        Some(material) => material,
+        None => return,
+    };
+    if let Some(texture_handle) = material.texture {
+        if let Some(texture) = textures.get(&texture_handle) {
+            sprite.size = texture.size;
+        }
+    }
+}
+
+// Runs the whole game loop without a window, one fixed tick per step
+pub struct HeadlessGame {
+    app: App,
+}
+
+impl HeadlessGame {
+    pub fn new(tick_seconds: f32, game: GamePlugin) -> HeadlessGame {
+        let mut builder = App::build();
+        builder
+            .add_plugin(HeadlessPlugin { tick_seconds })
+            .add_plugin(game);
+        let mut app = std::mem::take(&mut builder.app);
+        app.startup_schedule.initialize(&mut app.resources);
+        app.startup_schedule.run(&mut app.world, &mut app.resources);
+        let mut game = HeadlessGame { app };
+        // Assets load on other threads, the game starts in the menu once they are in
+        while game.state() == GameState::Loading {
+            std::thread::sleep(Duration::from_millis(1));
+            game.step(1);
+        }
+        game
+    }
+
+    pub fn step(&mut self, frames: u32) {
+        for _ in 0..frames {
+            self.app.update();
+        }
+    }
+
+    // Press and release a key over two frames, like a quick tap on the keyboard
+    pub fn tap_key(&mut self, key_code: KeyCode) {
+        self.send_key(key_code, ElementState::Pressed);
+        self.step(1);
+        self.send_key(key_code, ElementState::Released);
+        self.step(1);
+    }
+
+    fn send_key(&mut self, key_code: KeyCode, state: ElementState) {
+        let mut events = self
+            .app
+            .resources
+            .get_mut::<Events<KeyboardInput>>()
+            .unwrap();
+        events.send(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key_code),
+            state,
+        });
+    }
+
+    pub fn game_data(&self) -> ResourceRef<GameData> {
+        self.app.resources.get::<GameData>().unwrap()
+    }
+
+    pub fn player_translation(&self) -> Option<Vec3> {
+        let mut query = self.app.world.query::<(&Player, &Translation)>();
+        query
+            .iter()
+            .next()
+            .map(|(_player, translation)| translation.0)
+    }
+
+    pub fn state(&self) -> GameState {
+        self.app
+            .resources
+            .get::<GameStateMachine>()
+            .unwrap()
+            .current()
+    }
+
+    pub fn set_player_control(&mut self, player_control: PlayerControl) {
+        self.app.resources.insert(player_control);
+    }
+
+    // Used up by the bird on the next fixed tick
+    pub fn request_jump(&mut self, requested: bool) {
+        let mut jump_input = self.app.resources.get_mut::<JumpInput>().unwrap();
+        jump_input.requested = requested;
+    }
+
+    // Ends the run like a death would, without it counting as one.
+    // Pipes are cleared and a new run can start from there
+    pub fn abandon_run(&mut self) {
+        let mut state = self.app.resources.get_mut::<GameStateMachine>().unwrap();
+        if let GameState::Playing | GameState::Paused = state.current() {
+            state.set(GameState::Dead);
+        }
+    }
+
+    // Finished runs are still kept in memory, see last_replay
+    pub fn save_replays(&mut self, save: bool) {
+        self.app.resources.get_mut::<ReplayState>().unwrap().save = save;
+    }
+
+    pub fn last_replay(&self) -> Option<Replay> {
+        self.app
+            .resources
+            .get::<ReplayState>()
+            .unwrap()
+            .last()
+            .cloned()
+    }
+
+    // The seed of the next run is drawn from this
+    pub fn reseed(&mut self, seed: u64) {
+        self.app.resources.insert(GameRng::from_seed(seed));
+    }
+
+    pub fn player_velocity(&self) -> Option<Vec2> {
+        let mut query = self.app.world.query::<(&Player, &Velocity)>();
+        query.iter().next().map(|(_player, velocity)| velocity.0)
+    }
+
+    pub fn next_gap(&self) -> Option<Gap> {
+        let player_x = self.player_translation()?.x();
+        let mut pipe_query = self.app.world.query::<(&Pipe, &Translation, &Collider)>();
+        let pipes: Vec<(Vec3, Vec2)> = pipe_query
+            .iter()
+            .map(|(_pipe, translation, collider)| (translation.0, collider.shape.size()))
+            .collect();
+        let mut collider_query = self.app.world.query::<(&Collider, &Translation)>();
+        let score_colliders: Vec<Vec3> = collider_query
+            .iter()
+            .filter(|(collider, _translation)| collider.kind == ColliderKind::ScoreGiver)
+            .map(|(_collider, translation)| translation.0)
+            .collect();
+        next_gap(player_x, &pipes, &score_colliders)
+    }
+
+    // Pipes in play, not the ones waiting in the pool
+    pub fn pipe_translations(&self) -> Vec<Vec3> {
+        let mut query = self.app.world.query::<(&Pipe, &Translation, &Pooled)>();
+        query
+            .iter()
+            .filter(|(_pipe, _translation, pooled)| pooled.is_active())
+            .map(|(_pipe, translation, _pooled)| translation.0)
+            .collect()
+    }
+
+    // Should stay bounded no matter how long the game runs
+    pub fn entity_count(&self) -> usize {
+        self.app.world.query::<Entity>().iter().count()
+    }
+
+    pub fn material_count(&self) -> usize {
+        self.app
+            .resources
+            .get::<Assets<ColorMaterial>>()
+            .unwrap()
+            .iter()
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: f32 = 1.0 / 60.0;
+
+    fn autopilot_game(seed: u64) -> HeadlessGame {
+        HeadlessGame::new(
+            TICK,
+            GamePlugin {
+                seed,
+                replay: None,
+                autopilot: true,
+            },
+        )
+    }
+
+    // Steps until the run is over, false if it didn't end in time
+    fn play_until_dead(game: &mut HeadlessGame, mut frame: impl FnMut(&mut HeadlessGame)) -> bool {
+        for _ in 0..6000 {
+            if game.state() == GameState::Dead {
+                return true;
+            }
+            frame(game);
+        }
+        false
+    }
+
+    #[test]
+    fn replay_ends_like_the_recorded_run() {
+        let mut game = HeadlessGame::new(
+            TICK,
+            GamePlugin {
+                seed: 11,
+                replay: None,
+                autopilot: false,
+            },
+        );
+        game.save_replays(false);
+        // Leave the menu, then jump every so often until the bird hits something
+        game.tap_key(KeyCode::Space);
+        let mut frames = 0;
+        assert!(play_until_dead(&mut game, |game| {
+            frames += 1;
+            match frames % 24 {
+                0 => game.tap_key(KeyCode::Space),
+                _ => game.step(1),
+            }
+        }));
+        let recorded = game.last_replay().unwrap();
+        assert!(!recorded.jumps.is_empty());
+
+        let mut playback = HeadlessGame::new(
+            TICK,
+            GamePlugin {
+                seed: 0,
+                replay: Some(recorded.clone()),
+                autopilot: false,
+            },
+        );
+        playback.save_replays(false);
+        // Replays start on their own
+        assert!(play_until_dead(&mut playback, |game| game.step(1)));
+        let played = playback.last_replay().unwrap();
+        assert_eq!(played.score, recorded.score);
+        assert_eq!(played.death_tick, recorded.death_tick);
+        assert_eq!(played.jumps, recorded.jumps);
+    }
+
+    #[test]
+    fn entity_and_material_counts_level_off() {
+        let mut game = autopilot_game(7);
+        game.step(3000);
+        let (entities, materials) = (game.entity_count(), game.material_count());
+        game.step(3000);
+        // Some sprites can be on screen on one check and not the other, but nothing may pile up
+        assert!(game.entity_count() <= entities + 5);
+        assert_eq!(game.material_count(), materials);
+    }
+}
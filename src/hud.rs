@@ -0,0 +1,136 @@
+use crate::collision;
+use crate::gamedata;
+use crate::gamestate;
+use crate::leaderboard;
+use crate::loading;
+use crate::tween;
+use bevy::prelude::*;
+
+use collision::*;
+use gamedata::*;
+use gamestate::*;
+use leaderboard::*;
+use loading::*;
+use tween::*;
+
+// Live score while playing
+pub struct ScoreText;
+// Final and best score, shown with the end screen
+pub struct FinalScoreText;
+
+// The score text grows to this and shrinks back when scoring
+const SCORE_POP_SCALE: f32 = 1.4;
+const SCORE_POP_SECONDS: f32 = 0.3;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_system(score_text_system.system())
+            .add_system(score_pop_system.system())
+            .add_system(final_score_text_system.system());
+    }
+}
+
+fn setup(mut commands: Commands, game_assets: Res<GameAssets>) {
+    let font_handle = game_assets.font;
+    commands
+        .spawn(UiCameraComponents::default())
+        .spawn(TextComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(30.0),
+                    left: Val::Percent(48.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: "0".to_string(),
+                font: font_handle,
+                style: TextStyle {
+                    font_size: 120.0,
+                    color: Color::WHITE,
+                },
+            },
+            draw: Draw {
+                is_transparent: true,
+                is_visible: false,
+                render_commands: Vec::new(),
+            },
+            ..Default::default()
+        })
+        .with(ScoreText)
+        .spawn(TextComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Percent(20.0),
+                    left: Val::Percent(35.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font: font_handle,
+                style: TextStyle {
+                    font_size: 80.0,
+                    color: Color::WHITE,
+                },
+            },
+            draw: Draw {
+                is_transparent: true,
+                is_visible: false,
+                render_commands: Vec::new(),
+            },
+            ..Default::default()
+        })
+        .with(FinalScoreText);
+}
+
+fn score_text_system(
+    state: Res<GameStateMachine>,
+    game_data: Res<GameData>,
+    _score_text: &ScoreText,
+    mut text: Mut<Text>,
+    mut draw: Mut<Draw>,
+) {
+    draw.is_visible = state.current() == GameState::Playing;
+    text.value = game_data.score.to_string();
+}
+
+fn score_pop_system(
+    mut commands: Commands,
+    mut passed_gap_event_reader: Local<EventReader<PlayerPassedGap>>,
+    passed_gap_events: Res<Events<PlayerPassedGap>>,
+    mut query: Query<(&ScoreText, Entity)>,
+) {
+    if passed_gap_event_reader.iter(&passed_gap_events).count() == 0 {
+        return;
+    }
+    for (_score_text, entity) in &mut query.iter() {
+        let pop = TweenTarget::Scale {
+            from: SCORE_POP_SCALE,
+            to: 1.0,
+        };
+        commands.insert_one(entity, Tween::new(pop, SCORE_POP_SECONDS, Ease::BackOut));
+    }
+}
+
+fn final_score_text_system(
+    state: Res<GameStateMachine>,
+    game_data: Res<GameData>,
+    leaderboard: Res<Leaderboard>,
+    _final_score_text: &FinalScoreText,
+    mut text: Mut<Text>,
+    mut draw: Mut<Draw>,
+) {
+    draw.is_visible = state.current() == GameState::Dead;
+    text.value = format!(
+        "score {}   best {}",
+        game_data.score, leaderboard.best_score
+    );
+}
@@ -0,0 +1,238 @@
+use crate::bird;
+use crate::gamestate;
+use crate::rng;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bird::*;
+use gamestate::*;
+use rng::*;
+
+const MAX_ENTRIES: usize = 10;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: i32,
+    // Seconds since unix epoch
+    pub date: u64,
+    // Seed of the run, to replay the same pipes
+    pub seed: u64,
+}
+
+// Best score and the top runs on this machine
+#[derive(Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub best_score: i32,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    // Lives in the user data directory, e.g ~/.local/share/flappy_bevy/leaderboard.ron
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("flappy_bevy")
+            .join("leaderboard.ron")
+    }
+
+    // Never fails, a missing file is an empty leaderboard.
+    // A broken file is moved aside so it isn't overwritten, and we start over
+    pub fn load<P: AsRef<Path>>(path: P) -> Leaderboard {
+        let path = path.as_ref();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return Leaderboard::default(),
+        };
+        let mut leaderboard = match ron::de::from_str::<Leaderboard>(&text) {
+            Ok(leaderboard) => leaderboard,
+            Err(err) => {
+                println!("leaderboard {} is corrupt: {}", path.display(), err);
+                let corrupt_path = path.with_extension("ron.corrupt");
+                if let Err(err) = fs::rename(path, &corrupt_path) {
+                    println!("could not move corrupt leaderboard aside: {}", err);
+                }
+                return Leaderboard::default();
+            }
+        };
+        // Hand edited files can still hold nonsense
+        leaderboard.entries.retain(|entry| entry.score >= 0);
+        leaderboard.sort();
+        for entry in leaderboard.entries.iter() {
+            leaderboard.best_score = leaderboard.best_score.max(entry.score);
+        }
+        leaderboard.best_score = leaderboard.best_score.max(0);
+        leaderboard
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("could not create {}: {}", dir.display(), err))?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| format!("could not serialize leaderboard: {}", err))?;
+        // Write next to it first, so a crash mid write doesn't lose the old file
+        let temp_path = path.with_extension("ron.tmp");
+        fs::write(&temp_path, text)
+            .map_err(|err| format!("could not write {}: {}", temp_path.display(), err))?;
+        fs::rename(&temp_path, path)
+            .map_err(|err| format!("could not write {}: {}", path.display(), err))
+    }
+
+    // Returns the placement, starting at 0, if the entry made it onto the board
+    pub fn submit(&mut self, entry: LeaderboardEntry) -> Option<usize> {
+        self.best_score = self.best_score.max(entry.score);
+        let placement = self
+            .entries
+            .iter()
+            .position(|other| entry.score > other.score)
+            .unwrap_or(self.entries.len());
+        if placement >= MAX_ENTRIES {
+            return None;
+        }
+        self.entries.insert(placement, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(placement)
+    }
+
+    fn sort(&mut self) {
+        self.entries.sort_by(|a, b| b.score.cmp(&a.score));
+        self.entries.truncate(MAX_ENTRIES);
+    }
+}
+
+pub struct LeaderboardPath(pub PathBuf);
+
+pub struct PlayerName(pub String);
+
+pub struct LeaderboardPlugin {
+    pub player_name: String,
+}
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let path = Leaderboard::default_path();
+        app.add_resource(Leaderboard::load(&path))
+            .add_resource(LeaderboardPath(path))
+            .add_resource(PlayerName(self.player_name.clone()))
+            .add_system(leaderboard_system.system());
+    }
+}
+
+fn leaderboard_system(
+    mut run_event_reader: Local<EventReader<RunEvent>>,
+    run_events: Res<Events<RunEvent>>,
+    player_control: Res<PlayerControl>,
+    player_name: Res<PlayerName>,
+    leaderboard_path: Res<LeaderboardPath>,
+    rng: Res<GameRng>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    for run_event in run_event_reader.iter(&run_events) {
+        let score = match run_event {
+            RunEvent::Ended { score, .. } => *score,
+            RunEvent::Started => continue,
+        };
+        // Only the player's own runs count, not replays, the autopilot or training
+        if *player_control != PlayerControl::Keyboard {
+            continue;
+        }
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let placement = leaderboard.submit(LeaderboardEntry {
+            name: player_name.0.clone(),
+            score,
+            date,
+            seed: rng.seed,
+        });
+        if let Some(placement) = placement {
+            println!("leaderboard place {}: {}", placement + 1, score);
+        }
+        if let Err(err) = leaderboard.save(&leaderboard_path.0) {
+            println!("{}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory for every test, so they can run in parallel
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "flappy_bevy_leaderboard_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(score: i32) -> LeaderboardEntry {
+        LeaderboardEntry {
+            name: "player".to_string(),
+            score,
+            date: 0,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn corrupt_file_is_moved_aside() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join("leaderboard.ron");
+        fs::write(&path, "this isn't ron at all {").unwrap();
+        let leaderboard = Leaderboard::load(&path);
+        assert_eq!(leaderboard.best_score, 0);
+        assert!(leaderboard.entries.is_empty());
+        assert!(!path.exists());
+        assert!(dir.join("leaderboard.ron.corrupt").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn negative_scores_are_dropped() {
+        let dir = temp_dir("negative");
+        let path = dir.join("leaderboard.ron");
+        let mut leaderboard = Leaderboard {
+            best_score: -7,
+            entries: vec![entry(12), entry(-5), entry(8)],
+        };
+        leaderboard.save(&path).unwrap();
+        leaderboard = Leaderboard::load(&path);
+        let scores: Vec<i32> = leaderboard
+            .entries
+            .iter()
+            .map(|entry| entry.score)
+            .collect();
+        assert_eq!(scores, vec![12, 8]);
+        assert_eq!(leaderboard.best_score, 12);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn submit_keeps_the_top_entries() {
+        let mut leaderboard = Leaderboard::default();
+        for score in 1..=10 {
+            assert!(leaderboard.submit(entry(score * 10)).is_some());
+        }
+        // Below the 10th place
+        assert_eq!(leaderboard.submit(entry(5)), None);
+        assert_eq!(leaderboard.entries.len(), MAX_ENTRIES);
+        assert_eq!(leaderboard.submit(entry(55)), Some(5));
+        assert_eq!(leaderboard.entries.len(), MAX_ENTRIES);
+        assert_eq!(leaderboard.entries.last().unwrap().score, 20);
+        assert_eq!(leaderboard.best_score, 100);
+    }
+}
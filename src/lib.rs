@@ -0,0 +1,29 @@
+// The game as a library, so it can be driven from outside, like training agents on FlappyEnv
+pub mod animation;
+pub mod animation_state;
+pub mod autopilot;
+pub mod bird;
+pub mod bounds_deletion;
+pub mod collision;
+pub mod debug;
+pub mod env;
+pub mod game;
+pub mod gamedata;
+pub mod gamestate;
+pub mod ghost;
+pub mod headless;
+pub mod hud;
+pub mod leaderboard;
+pub mod loading;
+pub mod options;
+pub mod parallax;
+pub mod physics;
+pub mod pipes;
+pub mod playfield;
+pub mod pool;
+pub mod replay;
+pub mod rng;
+pub mod screens;
+pub mod time_control;
+pub mod tuning;
+pub mod tween;
@@ -0,0 +1,194 @@
+use crate::animation;
+use crate::gamestate;
+use crate::parallax;
+use crate::physics;
+use bevy::{
+    asset::{HandleId, LoadState},
+    prelude::*,
+    text::Font,
+};
+use std::collections::HashMap;
+
+use animation::*;
+use gamestate::*;
+use parallax::*;
+use physics::*;
+
+const BAR_WIDTH: f32 = 800.0;
+const BAR_HEIGHT: f32 = 40.0;
+
+// Everything the game draws, loaded once while in GameState::Loading
+pub struct GameAssets {
+    pub bird_texture: Handle<Texture>,
+    pub bird_atlas: Handle<TextureAtlas>,
+    pub bird_animations: Handle<AnimationSet>,
+    pub pipe_texture: Handle<Texture>,
+    // By path, for the layers in the parallax file
+    pub parallax_textures: HashMap<String, Handle<Texture>>,
+    pub start_screen_texture: Handle<Texture>,
+    pub game_over_texture: Handle<Texture>,
+    pub font: Handle<Font>,
+    // With their paths, to tell which one failed
+    loading: Vec<(HandleId, String)>,
+}
+
+fn load<T: 'static>(
+    asset_server: &AssetServer,
+    loading: &mut Vec<(HandleId, String)>,
+    path: &str,
+) -> Handle<T> {
+    let handle: Handle<T> = asset_server.load(path).unwrap();
+    loading.push((handle.id, path.to_string()));
+    handle
+}
+
+impl FromResources for GameAssets {
+    fn from_resources(resources: &Resources) -> Self {
+        let asset_server = resources.get::<AssetServer>().unwrap();
+        let parallax_config = resources.get::<ParallaxConfig>().unwrap();
+        let mut loading = Vec::new();
+        GameAssets {
+            // The atlas needs the size of the sheet, it's made once that is loaded
+            bird_atlas: Handle::default(),
+            bird_texture: load(&asset_server, &mut loading, "assets/bird.png"),
+            bird_animations: load(&asset_server, &mut loading, "assets/bird.anim"),
+            pipe_texture: load(&asset_server, &mut loading, "assets/pipe.png"),
+            parallax_textures: parallax_config
+                .texture_paths()
+                .into_iter()
+                .map(|path| (path.clone(), load(&asset_server, &mut loading, &path)))
+                .collect(),
+            start_screen_texture: load(&asset_server, &mut loading, "assets/SpaceToStart.png"),
+            game_over_texture: load(&asset_server, &mut loading, "assets/GameOverText.png"),
+            font: load(
+                &asset_server,
+                &mut loading,
+                "assets/fonts/Kenney Future Narrow.ttf",
+            ),
+            loading,
+        }
+    }
+}
+
+impl GameAssets {
+    // How many of the assets are loaded, 0 -> 1
+    pub fn progress(&self, asset_server: &AssetServer) -> f32 {
+        let loaded = self
+            .loading
+            .iter()
+            .filter(|(handle_id, _path)| {
+                matches!(
+                    asset_server.get_load_state_untyped(*handle_id),
+                    Some(LoadState::Loaded(_))
+                )
+            })
+            .count();
+        loaded as f32 / self.loading.len() as f32
+    }
+
+    // The path of an asset that couldn't be loaded
+    pub fn failed(&self, asset_server: &AssetServer) -> Option<&str> {
+        self.loading
+            .iter()
+            .find(|(handle_id, _path)| {
+                matches!(
+                    asset_server.get_load_state_untyped(*handle_id),
+                    Some(LoadState::Failed(_))
+                )
+            })
+            .map(|(_handle_id, path)| path.as_str())
+    }
+}
+
+// The empty and the filled part of the progress bar
+struct LoadingBar;
+struct LoadingBarFill;
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GameAssets>()
+            .add_startup_system(setup.system())
+            .add_system(loading_system.system())
+            .on_exit(GameState::Loading, loading_done_system.system());
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut fixed_timestep: ResMut<FixedTimestep>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    // Nothing spawns or moves until the assets are in, so runs don't depend on how long loading took
+    fixed_timestep.paused = true;
+    commands
+        .spawn(SpriteComponents {
+            material: materials.add(Color::rgb(0.2, 0.2, 0.2).into()),
+            sprite: Sprite {
+                size: Vec2::new(BAR_WIDTH, BAR_HEIGHT),
+            },
+            translation: Translation::new(0.0, 0.0, 10.0),
+            ..Default::default()
+        })
+        .with(LoadingBar)
+        .spawn(SpriteComponents {
+            material: materials.add(Color::WHITE.into()),
+            sprite: Sprite {
+                size: Vec2::new(0.0, BAR_HEIGHT),
+            },
+            translation: Translation::new(-BAR_WIDTH * 0.5, 0.0, 11.0),
+            ..Default::default()
+        })
+        .with(LoadingBarFill);
+}
+
+fn loading_system(
+    asset_server: Res<AssetServer>,
+    textures: Res<Assets<Texture>>,
+    mut game_assets: ResMut<GameAssets>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut state: ResMut<GameStateMachine>,
+    mut fill_query: Query<(&LoadingBarFill, &mut Sprite, &mut Translation)>,
+) {
+    if state.current() != GameState::Loading {
+        return;
+    }
+    if let Some(path) = game_assets.failed(&asset_server) {
+        panic!("could not load {}", path);
+    }
+    let progress = game_assets.progress(&asset_server);
+    // Grows to the right from the left end of the bar
+    for (_fill, mut sprite, mut translation) in &mut fill_query.iter() {
+        let width = BAR_WIDTH * progress;
+        sprite.size.set_x(width);
+        translation.0.set_x((width - BAR_WIDTH) * 0.5);
+    }
+    if progress < 1.0 {
+        return;
+    }
+    let bird_texture = textures.get(&game_assets.bird_texture).unwrap();
+    // 2x2 frames
+    game_assets.bird_atlas = texture_atlases.add(TextureAtlas::from_grid(
+        game_assets.bird_texture,
+        bird_texture.size,
+        2,
+        2,
+    ));
+    state.set(GameState::Menu);
+}
+
+fn loading_done_system(
+    mut commands: Commands,
+    mut fixed_timestep: ResMut<FixedTimestep>,
+    mut bar_query: Query<(&LoadingBar, Entity)>,
+    mut fill_query: Query<(&LoadingBarFill, Entity)>,
+) {
+    fixed_timestep.paused = false;
+    for (_bar, entity) in &mut bar_query.iter() {
+        commands.despawn(entity);
+    }
+    for (_fill, entity) in &mut fill_query.iter() {
+        commands.despawn(entity);
+    }
+}
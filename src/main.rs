@@ -1,58 +1,68 @@
 use bevy::prelude::*;
-mod animation;
-mod bird;
-mod bounds_deletion;
-mod clouds;
-mod gamedata;
-mod gamestate;
-mod mountains;
-mod physics;
-mod pipes;
-mod screens;
+use flappy_bevy::{
+    debug, game, headless, hud, leaderboard, options, playfield, replay, rng, time_control, tuning,
+};
 
-use animation::*;
-use bird::*;
-use clouds::*;
-use gamedata::*;
-use gamestate::*;
-use mountains::*;
-use physics::*;
-use pipes::*;
-use screens::*;
+use debug::*;
+use game::*;
+use headless::*;
+use hud::*;
+use leaderboard::*;
+use options::*;
+use playfield::*;
+use replay::*;
+use rng::*;
+use time_control::*;
+use tuning::*;
 
 fn main() {
+    let options = LaunchOptions::from_args();
+    let replay = options.replay.and_then(|path| match Replay::load(path) {
+        Ok(replay) => Some(replay),
+        Err(err) => {
+            println!("{}", err);
+            None
+        }
+    });
+    let seed = options.seed.unwrap_or_else(GameRng::random_seed);
+    println!("seed: {}", seed);
+    let game = GamePlugin {
+        seed,
+        replay,
+        autopilot: options.autopilot,
+    };
+    if let Some(frames) = options.headless_frames {
+        run_headless(frames, game);
+        return;
+    }
+
     App::build()
         .add_default_plugins()
-        .add_plugin(PipePlugin)
-        .add_plugin(BirdPlugin)
-        .add_plugin(CloudPlugin)
-        .add_plugin(MountainPlugin)
-        .add_plugin(AnimationPlugin)
-        .add_plugin(PhysicsPlugin)
-        .add_plugin(ScreensPlugin)
-        .add_plugin(GameStatePlugin)
-        .add_startup_system(setup.system())
-        .add_resource(ClearColor(Color::rgb(0.34, 0.75, 0.79)))
-        .add_resource(JumpHeight(23.0 * 40.0))
-        .add_resource(Gravity(45.0 * 40.0))
-        .add_resource(GameData {
-            game_state: GameState::Menu,
-            score: 0,
+        .add_plugin(game)
+        .add_plugin(LeaderboardPlugin {
+            player_name: options.player_name,
         })
+        .add_plugin(HudPlugin)
+        .add_plugin(TuningWatchPlugin)
+        .add_plugin(LetterboxPlugin)
+        .add_plugin(DebugPlugin)
+        .add_plugin(TimeControlPlugin)
+        .add_resource(ClearColor(Color::rgb(0.34, 0.75, 0.79)))
         .run();
 }
 
-fn setup(
-    mut commands: Commands,
-    mut asset_server: Res<AssetServer>,
-    mut textures: ResMut<Assets<Texture>>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-) {
-    commands.spawn(Camera2dComponents::default());
-    bird::spawn_bird(
-        &mut commands,
-        &mut asset_server,
-        &mut textures,
-        &mut texture_atlases,
+fn run_headless(frames: u32, game: GamePlugin) {
+    let mut game = HeadlessGame::new(1.0 / 60.0, game);
+    // Leave the menu
+    game.tap_key(KeyCode::Space);
+    game.step(frames);
+    println!(
+        "simulated {} frames, score: {}, player: {:?}, pipes: {}, entities: {}, materials: {}",
+        frames,
+        game.game_data().score,
+        game.player_translation(),
+        game.pipe_translations().len(),
+        game.entity_count(),
+        game.material_count()
     );
 }
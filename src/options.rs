@@ -0,0 +1,59 @@
+// Command line options, e.g: flappy_bevy --seed 42 --headless 600
+// or flappy_bevy --replay replays/last.ron, flappy_bevy --autopilot --headless 36000
+pub struct LaunchOptions {
+    // Amount of frames to simulate without a window
+    pub headless_frames: Option<u32>,
+    // Seed for the game rng, random when not given
+    pub seed: Option<u64>,
+    // Replay file to play back
+    pub replay: Option<String>,
+    // Name on the leaderboard
+    pub player_name: String,
+    // Let the autopilot play
+    pub autopilot: bool,
+}
+
+impl LaunchOptions {
+    pub fn from_args() -> LaunchOptions {
+        let mut options = LaunchOptions {
+            headless_frames: None,
+            seed: None,
+            replay: None,
+            player_name: std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_else(|_| "player".to_string()),
+            autopilot: false,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => {
+                    options.headless_frames = args.next().and_then(|frames| frames.parse().ok());
+                    if options.headless_frames.is_none() {
+                        println!("--headless expects a frame count, using 600");
+                        options.headless_frames = Some(600);
+                    }
+                }
+                "--seed" => {
+                    options.seed = args.next().and_then(|seed| seed.parse().ok());
+                    if options.seed.is_none() {
+                        println!("--seed expects a number, using a random seed");
+                    }
+                }
+                "--replay" => {
+                    options.replay = args.next();
+                    if options.replay.is_none() {
+                        println!("--replay expects a path to a replay file");
+                    }
+                }
+                "--name" => match args.next() {
+                    Some(name) => options.player_name = name,
+                    None => println!("--name expects a name"),
+                },
+                "--autopilot" => options.autopilot = true,
+                _ => println!("unknown argument: {}", arg),
+            }
+        }
+        options
+    }
+}
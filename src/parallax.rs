@@ -0,0 +1,274 @@
+use crate::bounds_deletion;
+use crate::gamestate;
+use crate::loading;
+use crate::physics;
+use crate::pipes;
+use crate::playfield;
+use crate::pool;
+use crate::rng;
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+use bounds_deletion::*;
+use gamestate::*;
+use loading::*;
+use physics::*;
+use pipes::*;
+use playfield::*;
+use pool::*;
+use rng::*;
+
+pub const PARALLAX_PATH: &str = "assets/parallax.ron";
+
+// One band of background or foreground sprites, spawned offscreen on the right
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ParallaxLayer {
+    pub name: String,
+    // One is picked at random for every sprite
+    pub textures: Vec<String>,
+    // Speed compared to the pipes: 1 moves with them, 0 stands still
+    pub depth: f32,
+    // Range for the sprite centers, in playfield units from the middle
+    pub y_range: (f32, f32),
+    pub scale_range: (f32, f32),
+    #[serde(default = "no_tint")]
+    pub tint: (f32, f32, f32),
+    pub z: f32,
+    pub spawn_seconds: f32,
+}
+
+fn no_tint() -> (f32, f32, f32) {
+    (1.0, 1.0, 1.0)
+}
+
+// Layers are spawned in this order every tick, they draw from the same rng as the pipes
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ParallaxConfig {
+    pub layers: Vec<ParallaxLayer>,
+}
+
+// The file as it ships, built in so the defaults aren't kept in two places
+const DEFAULT_PARALLAX: &str = include_str!("../assets/parallax.ron");
+
+impl Default for ParallaxConfig {
+    fn default() -> Self {
+        ParallaxConfig::parse(DEFAULT_PARALLAX, "built in").unwrap()
+    }
+}
+
+impl ParallaxConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ParallaxConfig, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read parallax {}: {}", path.display(), err))?;
+        ParallaxConfig::parse(&text, &path.display().to_string())
+    }
+
+    fn parse(text: &str, name: &str) -> Result<ParallaxConfig, String> {
+        let config: ParallaxConfig = ron::de::from_str(text)
+            .map_err(|err| format!("could not parse parallax {}: {}", name, err))?;
+        config
+            .validate()
+            .map_err(|err| format!("invalid parallax {}: {}", name, err))?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        for layer in self.layers.iter() {
+            if layer.textures.is_empty() {
+                errors.push(format!("{} has no textures", layer.name));
+            }
+            if layer.depth < 0.0 {
+                errors.push(format!("{} needs a depth of at least 0", layer.name));
+            }
+            if layer.y_range.0 > layer.y_range.1 {
+                errors.push(format!("{} needs y_range min <= max", layer.name));
+            }
+            if layer.scale_range.0 <= 0.0 || layer.scale_range.0 > layer.scale_range.1 {
+                errors.push(format!("{} needs 0 < scale_range min <= max", layer.name));
+            }
+            if layer.spawn_seconds <= 0.0 {
+                errors.push(format!("{} needs spawn_seconds above 0", layer.name));
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join(", ")),
+        }
+    }
+
+    // Every texture any layer uses, for the loading state
+    pub fn texture_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = Vec::new();
+        for layer in self.layers.iter() {
+            for path in layer.textures.iter() {
+                if !paths.contains(path) {
+                    paths.push(path.clone());
+                }
+            }
+        }
+        paths
+    }
+}
+
+// A spawn timer for each layer, in the order of the config
+pub struct ParallaxTimers(Vec<Timer>);
+
+impl ParallaxTimers {
+    fn new(config: &ParallaxConfig) -> ParallaxTimers {
+        ParallaxTimers(
+            config
+                .layers
+                .iter()
+                .map(|layer| Timer::from_seconds(layer.spawn_seconds, true))
+                .collect(),
+        )
+    }
+}
+
+// Swaps in a reloaded file. Textures are only loaded up front, so it can't use new ones
+pub fn replace_parallax(
+    config: &mut ParallaxConfig,
+    timers: &mut ParallaxTimers,
+    game_assets: &GameAssets,
+    new_config: ParallaxConfig,
+) -> Result<(), String> {
+    for path in new_config.texture_paths() {
+        if !game_assets.parallax_textures.contains_key(&path) {
+            return Err(format!(
+                "{} isn't loaded, new textures need a restart",
+                path
+            ));
+        }
+    }
+    *timers = ParallaxTimers::new(&new_config);
+    *config = new_config;
+    Ok(())
+}
+
+// Scrolls with the pipes, scaled by the depth of its layer
+pub struct ParallaxSprite {
+    pub depth: f32,
+}
+
+// Loads the parallax file, a missing or broken file falls back to the defaults
+pub struct ParallaxPlugin;
+
+impl Plugin for ParallaxPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let config = match ParallaxConfig::load(PARALLAX_PATH) {
+            Ok(config) => config,
+            Err(err) => {
+                println!("{}, using default parallax", err);
+                ParallaxConfig::default()
+            }
+        };
+        app.add_resource(ParallaxTimers::new(&config))
+            .add_resource(config)
+            .add_fixed_system(parallax_spawn_system.system())
+            .add_fixed_system_to_stage(FIXED_PRE_UPDATE, parallax_scroll_system.system())
+            .add_system_to_stage(RUN_START, reset_parallax_timers_system.system());
+    }
+}
+
+// Restart the timers with each run, so the spawns line up with the pipes
+fn reset_parallax_timers_system(
+    mut run_event_reader: Local<EventReader<RunEvent>>,
+    run_events: Res<Events<RunEvent>>,
+    mut timers: ResMut<ParallaxTimers>,
+) {
+    for run_event in run_event_reader.iter(&run_events) {
+        if let RunEvent::Started = run_event {
+            for timer in timers.0.iter_mut() {
+                timer.reset();
+            }
+        }
+    }
+}
+
+// gen_range panics on an empty range, a fixed value is fine here
+fn pick(rng: &mut GameRng, range: (f32, f32)) -> f32 {
+    match range.0 < range.1 {
+        true => rng.gen_range(range.0, range.1),
+        false => range.0,
+    }
+}
+
+fn parallax_spawn_system(
+    mut commands: Commands,
+    fixed_timestep: Res<FixedTimestep>,
+    playfield: Res<Playfield>,
+    config: Res<ParallaxConfig>,
+    game_assets: Res<GameAssets>,
+    textures: Res<Assets<Texture>>,
+    mut timers: ResMut<ParallaxTimers>,
+    mut pool: ResMut<EntityPool>,
+    mut shared_materials: ResMut<SharedMaterials>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut rng: ResMut<GameRng>,
+) {
+    for (layer, timer) in config.layers.iter().zip(timers.0.iter_mut()) {
+        timer.tick(fixed_timestep.step);
+        if !timer.finished {
+            continue;
+        }
+        // Only draw from the rng when spawning, so the sequence doesn't depend on frame rate
+        let index = rng.gen_range(0, layer.textures.len());
+        let texture = game_assets.parallax_textures[&layer.textures[index]];
+        let scale = pick(&mut *rng, layer.scale_range);
+        let y = pick(&mut *rng, layer.y_range);
+        let half_width = textures.get(&texture).unwrap().size.x() * scale * 0.5;
+        let (r, g, b) = layer.tint;
+        let sprite = pool.spawn(
+            &mut commands,
+            PoolKind::Parallax,
+            SpriteComponents {
+                material: shared_materials.get(&mut materials, texture, Color::rgb(r, g, b)),
+                scale: Scale(scale),
+                // Just out of sight on the right
+                translation: Translation(Vec3::new(
+                    playfield.half_width() + half_width,
+                    y,
+                    layer.z,
+                )),
+                ..Default::default()
+            },
+        );
+        commands.insert(
+            sprite,
+            (
+                ParallaxSprite { depth: layer.depth },
+                BoundsDeletion::new(Edges::LEFT),
+                Velocity(Vec2::zero()),
+            ),
+        );
+    }
+}
+
+// The world moves at the speed of the pipes, which can change with the tuning
+fn parallax_scroll_system(
+    pipe_settings: Res<PipeSpawnSettings>,
+    parallax_sprite: &ParallaxSprite,
+    pooled: &Pooled,
+    mut velocity: Mut<Velocity>,
+) {
+    // Parked sprites stay where they are
+    if pooled.is_active() {
+        velocity.0 = Vec2::new(pipe_settings.speed * parallax_sprite.depth, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_parallax_is_valid() {
+        let config = ParallaxConfig::default();
+        assert!(!config.layers.is_empty());
+        assert!(config.validate().is_ok());
+    }
+}
@@ -1,32 +1,236 @@
-use bevy::prelude::*;
+use bevy::{
+    app::stage,
+    ecs::{Resources, Schedule, System, World},
+    prelude::*,
+};
 
 pub struct Velocity(pub Vec2);
 
 pub struct Gravity(pub f32);
 pub struct AffectedByGravity;
 
+// Main schedule stages around the fixed update
+pub const FIXED_RESTORE: &str = "fixed_restore";
+pub const FIXED_RUN: &str = "fixed_run";
+pub const FIXED_INTERPOLATE: &str = "fixed_interpolate";
+
+// Stages inside the fixed schedule, run once per fixed tick in this order
+pub const FIXED_PRE_UPDATE: &str = "fixed_pre_update";
+pub const FIXED_PHYSICS: &str = "fixed_physics";
+// Collisions are found here after moving, and handled in FIXED_UPDATE
+pub const FIXED_COLLISION: &str = "fixed_collision";
+pub const FIXED_UPDATE: &str = "fixed_update";
+pub const FIXED_POST_UPDATE: &str = "fixed_post_update";
+
+// Don't try to catch up forever after a long hitch
+const MAX_STEPS_PER_FRAME: u32 = 8;
+
+pub struct FixedTimestep {
+    // Seconds simulated by one fixed tick
+    pub step: f32,
+    // Index of the fixed tick currently running, counts up forever
+    pub tick: u64,
+    // How far we are between the previous and the current tick, 0 -> 1
+    pub alpha: f32,
+    // No ticks run while paused, everything stays where it is
+    pub paused: bool,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub fn new(step: f32) -> FixedTimestep {
+        FixedTimestep {
+            step,
+            tick: 0,
+            alpha: 0.0,
+            paused: false,
+            accumulator: 0.0,
+        }
+    }
+
+    // Add frame time, returns how many ticks should run this frame
+    fn advance(&mut self, delta_seconds: f32) -> u32 {
+        self.accumulator += delta_seconds;
+        let mut steps = (self.accumulator / self.step) as u32;
+        if steps > MAX_STEPS_PER_FRAME {
+            steps = MAX_STEPS_PER_FRAME;
+            self.accumulator = self.step * steps as f32;
+        }
+        self.accumulator -= self.step * steps as f32;
+        self.alpha = self.accumulator / self.step;
+        steps
+    }
+}
+
+// Holds the systems added with add_fixed_system.
+// Taken out while running, so the systems can borrow the other resources
+pub struct FixedSchedule(Option<Schedule>);
+
+impl Default for FixedSchedule {
+    fn default() -> Self {
+        let mut schedule = Schedule::default();
+        schedule.add_stage(FIXED_PRE_UPDATE);
+        schedule.add_stage(FIXED_PHYSICS);
+        schedule.add_stage(FIXED_COLLISION);
+        schedule.add_stage(FIXED_UPDATE);
+        schedule.add_stage(FIXED_POST_UPDATE);
+        FixedSchedule(Some(schedule))
+    }
+}
+
+pub trait FixedUpdateAppExt {
+    fn add_fixed_system(&mut self, system: Box<dyn System>) -> &mut Self;
+    fn add_fixed_system_to_stage(
+        &mut self,
+        stage_name: &'static str,
+        system: Box<dyn System>,
+    ) -> &mut Self;
+}
+
+impl FixedUpdateAppExt for AppBuilder {
+    fn add_fixed_system(&mut self, system: Box<dyn System>) -> &mut Self {
+        self.add_fixed_system_to_stage(FIXED_UPDATE, system)
+    }
+
+    fn add_fixed_system_to_stage(
+        &mut self,
+        stage_name: &'static str,
+        system: Box<dyn System>,
+    ) -> &mut Self {
+        let resources = self.resources_mut();
+        if !resources.contains::<FixedSchedule>() {
+            resources.insert(FixedSchedule::default());
+        }
+        let mut fixed_schedule = resources.get_mut::<FixedSchedule>().unwrap();
+        fixed_schedule
+            .0
+            .as_mut()
+            .unwrap()
+            .add_system_to_stage(stage_name, system);
+        drop(fixed_schedule);
+        self
+    }
+}
+
+// Render side position of things moved by the fixed update.
+// Translation holds the interpolated value between frames
+pub struct Interpolated {
+    previous: Vec3,
+    current: Vec3,
+    rendered: Vec3,
+}
+
+impl Interpolated {
+    pub fn new(translation: Vec3) -> Interpolated {
+        Interpolated {
+            previous: translation,
+            current: translation,
+            rendered: translation,
+        }
+    }
+
+    // Position at the start of the latest fixed tick
+    pub fn previous(&self) -> Vec3 {
+        self.previous
+    }
+}
+
 pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(velocity_system.system())
-            .add_system(gravity_system.system());
+        app.add_resource(FixedTimestep::new(1.0 / 60.0))
+            .add_stage_after(stage::UPDATE, FIXED_RESTORE)
+            .add_stage_after(FIXED_RESTORE, FIXED_RUN)
+            .add_stage_after(FIXED_RUN, FIXED_INTERPOLATE)
+            .add_system_to_stage(FIXED_RESTORE, restore_translation_system.system())
+            .add_system_to_stage(FIXED_RUN, fixed_update_system.thread_local_system())
+            .add_system_to_stage(FIXED_INTERPOLATE, interpolate_translation_system.system())
+            .add_system_to_stage(FIXED_INTERPOLATE, add_interpolation_system.system())
+            .add_fixed_system_to_stage(FIXED_PHYSICS, store_previous_system.system())
+            .add_fixed_system_to_stage(FIXED_PHYSICS, gravity_system.system())
+            .add_fixed_system_to_stage(FIXED_PHYSICS, velocity_system.system());
+    }
+}
+
+fn fixed_update_system(world: &mut World, resources: &mut Resources) {
+    let delta_seconds = resources.get::<Time>().unwrap().delta_seconds;
+    let steps = {
+        let mut fixed_timestep = resources.get_mut::<FixedTimestep>().unwrap();
+        if fixed_timestep.paused {
+            return;
+        }
+        fixed_timestep.advance(delta_seconds)
+    };
+    let schedule = resources.get_mut::<FixedSchedule>().unwrap().0.take();
+    let mut schedule = match schedule {
+        Some(schedule) => schedule,
+        None => return,
+    };
+    schedule.initialize(resources);
+    for _ in 0..steps {
+        schedule.run(world, resources);
+        resources.get_mut::<FixedTimestep>().unwrap().tick += 1;
+    }
+    resources.get_mut::<FixedSchedule>().unwrap().0 = Some(schedule);
+}
+
+// Put back the simulated position before ticking.
+// If something else moved the entity since last frame, treat it as a teleport
+fn restore_translation_system(
+    mut translation: Mut<Translation>,
+    mut interpolated: Mut<Interpolated>,
+) {
+    if translation.0 != interpolated.rendered {
+        interpolated.previous = translation.0;
+        interpolated.current = translation.0;
+    }
+    translation.0 = interpolated.current;
+}
+
+fn store_previous_system(translation: &Translation, mut interpolated: Mut<Interpolated>) {
+    interpolated.previous = translation.0;
+}
+
+fn interpolate_translation_system(
+    fixed_timestep: Res<FixedTimestep>,
+    mut translation: Mut<Translation>,
+    mut interpolated: Mut<Interpolated>,
+) {
+    interpolated.current = translation.0;
+    translation.0 = interpolated
+        .previous
+        .lerp(interpolated.current, fixed_timestep.alpha);
+    interpolated.rendered = translation.0;
+}
+
+// Anything that moves gets interpolated
+fn add_interpolation_system(
+    mut commands: Commands,
+    mut query: Query<Without<Interpolated, (Entity, &Translation, &Velocity)>>,
+) {
+    for (entity, translation, _velocity) in &mut query.iter() {
+        commands.insert_one(entity, Interpolated::new(translation.0));
     }
 }
 
 fn gravity_system(
     gravity: Res<Gravity>,
-    time: Res<Time>,
+    fixed_timestep: Res<FixedTimestep>,
     _affected_by_gravity: &AffectedByGravity,
     mut velocity: Mut<Velocity>,
 ) {
-    *velocity.0.y_mut() -= gravity.0 * time.delta_seconds;
+    *velocity.0.y_mut() -= gravity.0 * fixed_timestep.step;
 }
 
-fn velocity_system(time: Res<Time>, mut position: Mut<Translation>, velocity: Mut<Velocity>) {
+fn velocity_system(
+    fixed_timestep: Res<FixedTimestep>,
+    mut position: Mut<Translation>,
+    velocity: Mut<Velocity>,
+) {
     let y = position.0.y();
     let x = position.0.x();
-    let delta = time.delta_seconds;
+    let delta = fixed_timestep.step;
     position.0.set_y(y + velocity.0.y() * delta);
     position.0.set_x(x + velocity.0.x() * delta);
 }
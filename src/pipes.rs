@@ -1,13 +1,26 @@
+use crate::bird;
 use crate::bounds_deletion;
-use crate::gamedata;
+use crate::collision;
 use crate::gamestate;
+use crate::loading;
 use crate::physics;
+use crate::playfield;
+use crate::pool;
+use crate::rng;
 use bevy::prelude::*;
+use bird::*;
 use bounds_deletion::*;
-use gamedata::*;
+use collision::*;
 use gamestate::*;
+use loading::*;
 use physics::*;
-use rand::{thread_rng, Rng};
+use playfield::*;
+use pool::*;
+use rand::Rng;
+use rng::*;
+
+// Passing the score zone is checked against a thin line
+const SCORE_ZONE_WIDTH: f32 = 10.0;
 
 pub struct Pipe;
 
@@ -17,6 +30,16 @@ pub struct SpawnTimer {
     pub last_pos: f32,
 }
 
+impl Default for SpawnTimer {
+    fn default() -> Self {
+        SpawnTimer {
+            timer: Timer::from_seconds(2.0, true),
+            last_pos: 0.5,
+        }
+    }
+}
+
+// Comes from the tuning file
 pub struct PipeSpawnSettings {
     pub min_time: f32,
     pub max_time: f32,
@@ -27,52 +50,122 @@ pub struct PipeSpawnSettings {
     pub max_center_delta: f32,
 }
 
-#[derive(PartialEq)]
-pub enum Collider {
-    Solid,
-    ScoreGiver,
+// The opening of a pipe pair
+pub struct Gap {
+    pub x: f32,
+    pub center_y: f32,
+    pub height: f32,
 }
 
 pub struct PipePlugin;
 
 impl Plugin for PipePlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system(spawn_pipe_system.system())
-            .add_resource(SpawnTimer {
-                timer: Timer::from_seconds(2.0, true),
-                last_pos: 0.5,
-            })
-            .add_resource(PipeSpawnSettings {
-                min_time: 0.9,
-                max_time: 1.2,
-                speed: -700.0,
-                min_pipe_distance: 300.0,
-                max_pipe_distance: 600.0,
-                max_center_delta: 0.4,
-            });
+        app.add_fixed_system(spawn_pipe_system.system())
+            .on_enter(GameState::Dead, despawn_pipes_system.system())
+            .add_system_to_stage(RUN_START, reset_spawn_timer_system.system())
+            .add_resource(SpawnTimer::default());
+    }
+}
+
+// Every run spawns its pipes on the same ticks
+fn reset_spawn_timer_system(
+    mut run_event_reader: Local<EventReader<RunEvent>>,
+    run_events: Res<Events<RunEvent>>,
+    mut spawn_timer: ResMut<SpawnTimer>,
+) {
+    for run_event in run_event_reader.iter(&run_events) {
+        if let RunEvent::Started = run_event {
+            *spawn_timer = SpawnTimer::default();
+        }
+    }
+}
+
+fn despawn_pipes_system(
+    mut pool: ResMut<EntityPool>,
+    mut pipe_query: Query<(&Pipe, Entity)>,
+    mut score_collider_query: Query<(&Collider, Entity)>,
+) {
+    for (_pipe, pipe_entity) in &mut pipe_query.iter() {
+        pool.release(pipe_entity);
+    }
+    for (collider, score_entity) in &mut score_collider_query.iter() {
+        if collider.kind == ColliderKind::ScoreGiver {
+            pool.release(score_entity);
+        }
+    }
+}
+
+// The closest gap the player hasn't scored yet, found by its score collider.
+// Takes pipe positions with their sizes, and the score collider positions
+pub fn next_gap(player_x: f32, pipes: &[(Vec3, Vec2)], score_colliders: &[Vec3]) -> Option<Gap> {
+    let mut score_x: Option<f32> = None;
+    for translation in score_colliders.iter() {
+        if translation.x() < player_x {
+            continue;
+        }
+        if score_x.map_or(true, |x| translation.x() < x) {
+            score_x = Some(translation.x());
+        }
+    }
+    let score_x = score_x?;
+
+    // The pipes of that gap are the ones closest to its score collider
+    let mut closest_distance = std::f32::MAX;
+    for (translation, _size) in pipes.iter() {
+        closest_distance = closest_distance.min((translation.x() - score_x).abs());
+    }
+    let mut lowest: Option<f32> = None;
+    let mut highest: Option<f32> = None;
+    let mut pipe_height = 0.0;
+    let mut pipe_x = score_x;
+    for (translation, size) in pipes.iter() {
+        if (translation.x() - score_x).abs() > closest_distance + 1.0 {
+            continue;
+        }
+        let y = translation.y();
+        lowest = Some(lowest.map_or(y, |lowest| lowest.min(y)));
+        highest = Some(highest.map_or(y, |highest| highest.max(y)));
+        pipe_height = size.y();
+        pipe_x = translation.x();
     }
+    let (lowest, highest) = (lowest?, highest?);
+    Some(Gap {
+        x: pipe_x,
+        center_y: (lowest + highest) * 0.5,
+        height: highest - lowest - pipe_height,
+    })
+}
+
+// This is the extent from the center in Y, a pipe can go maximum, until it flies in the air
+pub fn gap_center_limit(playfield: &Playfield, pipe_height: f32) -> f32 {
+    playfield.half_height() - pipe_height * 0.5
 }
 
 fn spawn_pipe_system(
     mut commands: Commands,
     pipe_settings: Res<PipeSpawnSettings>,
-    game_data: Res<GameData>,
-    asset_server: Res<AssetServer>,
-    time: Res<Time>,
+    state: Res<GameStateMachine>,
+    game_assets: Res<GameAssets>,
+    fixed_timestep: Res<FixedTimestep>,
+    playfield: Res<Playfield>,
     mut spawn_timer: ResMut<SpawnTimer>,
+    mut pool: ResMut<EntityPool>,
+    mut shared_materials: ResMut<SharedMaterials>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    mut textures: ResMut<Assets<Texture>>,
+    textures: Res<Assets<Texture>>,
+    mut rng: ResMut<GameRng>,
 ) {
-    if game_data.game_state != GameState::Playing {
+    // Not on the tick of a death either, the pipes are cleared when entering GameState::Dead
+    if !state.is_settled_in(GameState::Playing) {
         return;
     }
 
-    spawn_timer.timer.tick(time.delta_seconds);
+    spawn_timer.timer.tick(fixed_timestep.step);
     if !spawn_timer.timer.finished {
         return;
     }
 
-    let mut rng = thread_rng();
     spawn_timer.timer.duration = rng.gen_range(pipe_settings.min_time, pipe_settings.max_time);
 
     let mut new_center_pos = spawn_timer.last_pos
@@ -81,36 +174,43 @@ fn spawn_pipe_system(
             pipe_settings.max_center_delta,
         );
 
-    // sorry for the hardcoded values
-    // This is the extent from the center in Y, a pipe can go maximum, until it flies in the air
-    let clamp_range = (1280.0 - (6.0 * 128.0)) / 1280.0;
+    let pipe_texture_handle = game_assets.pipe_texture;
+    let pipe_size = textures.get(&pipe_texture_handle).unwrap().size * SPRITE_SCALE;
+
+    let pipe_height = pipe_size.y();
+    let clamp_range = gap_center_limit(&playfield, pipe_height) / playfield.half_height();
 
     // Clamp func seem to be nightly only for now
     new_center_pos = new_center_pos.min(clamp_range);
     new_center_pos = new_center_pos.max(-clamp_range);
     spawn_timer.last_pos = new_center_pos;
     // to world units
-    new_center_pos *= 1280.0 * 0.5;
-
-    let pipe_texture_handle = asset_server
-        .load_sync(&mut textures, "assets/pipe.png")
-        .unwrap();
+    new_center_pos *= playfield.half_height();
 
-    let pipe_offset_y = (6.0 * 128.0) * 0.5;
-    let pipe_offset_x = (6.0 * 32.0) * 0.5;
+    let pipe_offset_y = pipe_height * 0.5;
+    let pipe_offset_x = pipe_size.x() * 0.5;
     let mut pipe_delta = rng.gen_range(
         pipe_settings.min_pipe_distance,
         pipe_settings.max_pipe_distance,
     );
     // half the size because both pipes will be offseted in opposide direction
     pipe_delta *= 0.5;
-    let x_pos = 1920.0 * 0.5 + pipe_offset_x;
+    let x_pos = playfield.half_width() + pipe_offset_x;
+    let pipe_material = shared_materials.get(&mut materials, pipe_texture_handle, Color::WHITE);
+    let pipe_collider = Collider::new(
+        ColliderKind::Solid,
+        Shape::Aabb {
+            half_size: pipe_size * 0.5,
+        },
+    );
 
     // lower pipe
-    commands
-        .spawn(SpriteComponents {
-            material: materials.add(pipe_texture_handle.into()),
-            scale: Scale(6.0),
+    let lower_pipe = pool.spawn(
+        &mut commands,
+        PoolKind::Pipe,
+        SpriteComponents {
+            material: pipe_material,
+            scale: Scale(SPRITE_SCALE),
             draw: Draw {
                 is_transparent: true,
                 is_visible: true,
@@ -118,16 +218,24 @@ fn spawn_pipe_system(
             },
             translation: Translation::new(x_pos, -pipe_offset_y + new_center_pos - pipe_delta, 3.0),
             ..Default::default()
-        })
-        .with(Velocity(Vec2::new(pipe_settings.speed, 0.0)))
-        .with(Pipe)
-        .with(OffsceenDeletion)
-        .with(Collider::Solid);
+        },
+    );
+    commands.insert(
+        lower_pipe,
+        (
+            Velocity(Vec2::new(pipe_settings.speed, 0.0)),
+            Pipe,
+            BoundsDeletion::new(Edges::LEFT),
+            pipe_collider,
+        ),
+    );
     // higher pipe
-    commands
-        .spawn(SpriteComponents {
-            material: materials.add(pipe_texture_handle.into()),
-            scale: Scale(6.0),
+    let higher_pipe = pool.spawn(
+        &mut commands,
+        PoolKind::Pipe,
+        SpriteComponents {
+            material: pipe_material,
+            scale: Scale(SPRITE_SCALE),
             draw: Draw {
                 is_transparent: true,
                 is_visible: true,
@@ -136,19 +244,33 @@ fn spawn_pipe_system(
             translation: Translation::new(x_pos, pipe_offset_y + new_center_pos + pipe_delta, 3.0),
             rotation: Rotation::from_rotation_z(std::f32::consts::PI),
             ..Default::default()
-        })
-        .with(Pipe)
-        .with(OffsceenDeletion)
-        .with(Velocity(Vec2::new(pipe_settings.speed, 0.0)))
-        .with(Collider::Solid);
+        },
+    );
+    commands.insert(
+        higher_pipe,
+        (
+            Velocity(Vec2::new(pipe_settings.speed, 0.0)),
+            Pipe,
+            BoundsDeletion::new(Edges::LEFT),
+            pipe_collider,
+        ),
+    );
 
     // score collider offseted by half player size
-    let score_offset = Vec3::new(32.0 * 6.0 * 0.5, 0.0, 0.0);
-    commands
-        .spawn((
+    let score_offset = Vec3::new(BIRD_SIZE * SPRITE_SCALE * 0.5, 0.0, 0.0);
+    let score_zone = pool.spawn(
+        &mut commands,
+        PoolKind::ScoreZone,
+        (
             Translation(score_offset + Vec3::new(x_pos, 0.0, 0.0)),
-            Collider::ScoreGiver,
+            Collider::new(
+                ColliderKind::ScoreGiver,
+                Shape::Aabb {
+                    half_size: Vec2::new(SCORE_ZONE_WIDTH * 0.5, playfield.half_height()),
+                },
+            ),
             Velocity(Vec2::new(pipe_settings.speed, 0.0)),
-        ))
-        .with(OffsceenDeletion);
+        ),
+    );
+    commands.insert_one(score_zone, BoundsDeletion::new(Edges::LEFT));
 }
@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+
+// The game is played in this area no matter the window size, in world units
+pub const VIRTUAL_WIDTH: f32 = 1920.0;
+pub const VIRTUAL_HEIGHT: f32 = 1280.0;
+// The pixel art is drawn this many times its texture size
+pub const SPRITE_SCALE: f32 = 6.0;
+
+// Bars are made this many playfields long, so even odd window shapes are covered
+const BAR_LENGTH: f32 = 10.0;
+// Above everything in the game, below the camera
+const BAR_Z: f32 = 500.0;
+
+// Bounds of the area the game is played in, centered on the origin.
+// Gameplay only uses this, so the window size never changes how the game plays
+pub struct Playfield {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Playfield {
+    fn default() -> Self {
+        Playfield {
+            width: VIRTUAL_WIDTH,
+            height: VIRTUAL_HEIGHT,
+        }
+    }
+}
+
+impl Playfield {
+    pub fn half_width(&self) -> f32 {
+        self.width * 0.5
+    }
+
+    pub fn half_height(&self) -> f32 {
+        self.height * 0.5
+    }
+}
+
+// The window the playfield is shown in.
+// scale is world units per window pixel, the camera is scaled by it
+pub struct Viewport {
+    pub width: f32,
+    pub height: f32,
+    pub scale: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            width: VIRTUAL_WIDTH,
+            height: VIRTUAL_HEIGHT,
+            scale: 1.0,
+        }
+    }
+}
+
+// Marks the camera showing the playfield
+pub struct PlayfieldCamera;
+
+// Scales the camera so the playfield always fits the window,
+// and covers what's outside of it with bars
+pub struct LetterboxPlugin;
+
+impl Plugin for LetterboxPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Viewport>()
+            .add_startup_system(spawn_bars_system.system())
+            .add_system(letterbox_system.system());
+    }
+}
+
+fn spawn_bars_system(
+    mut commands: Commands,
+    playfield: Res<Playfield>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let material = materials.add(Color::rgb(0.0, 0.0, 0.0).into());
+    let bar_width = playfield.width * BAR_LENGTH;
+    let bar_height = playfield.height * BAR_LENGTH;
+    // Left, right, bottom, top, each starting at the edge of the playfield
+    let bars = [
+        (
+            Vec2::new(-(playfield.half_width() + bar_width * 0.5), 0.0),
+            Vec2::new(bar_width, bar_height),
+        ),
+        (
+            Vec2::new(playfield.half_width() + bar_width * 0.5, 0.0),
+            Vec2::new(bar_width, bar_height),
+        ),
+        (
+            Vec2::new(0.0, -(playfield.half_height() + bar_height * 0.5)),
+            Vec2::new(playfield.width, bar_height),
+        ),
+        (
+            Vec2::new(0.0, playfield.half_height() + bar_height * 0.5),
+            Vec2::new(playfield.width, bar_height),
+        ),
+    ];
+    for (position, size) in bars.iter() {
+        commands.spawn(SpriteComponents {
+            material,
+            sprite: Sprite { size: *size },
+            translation: Translation::new(position.x(), position.y(), BAR_Z),
+            ..Default::default()
+        });
+    }
+}
+
+fn letterbox_system(
+    windows: Res<Windows>,
+    playfield: Res<Playfield>,
+    mut viewport: ResMut<Viewport>,
+    mut camera_query: Query<(&PlayfieldCamera, &mut Scale)>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let width = window.width as f32;
+    let height = window.height as f32;
+    if width <= 0.0 || height <= 0.0 {
+        return;
+    }
+    if width == viewport.width && height == viewport.height {
+        return;
+    }
+    // The side that fits worst decides, the other side gets bars
+    let scale = (playfield.width / width).max(playfield.height / height);
+    *viewport = Viewport {
+        width,
+        height,
+        scale,
+    };
+    for (_camera, mut camera_scale) in &mut camera_query.iter() {
+        camera_scale.0 = scale;
+    }
+}
@@ -0,0 +1,158 @@
+use crate::physics;
+use bevy::{ecs::DynamicBundle, prelude::*};
+use std::collections::HashMap;
+
+use physics::*;
+
+// Free entities wait here, far away from anything they could collide with
+const PARKED_POSITION: f32 = -100_000.0;
+
+// Entities of one kind all have the same components, so a free one can take any spawn of its kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PoolKind {
+    Pipe,
+    ScoreZone,
+    Parallax,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PoolState {
+    // Taken from the pool this tick, doesn't have its interpolation reset yet
+    Taken,
+    Active,
+    Free,
+}
+
+pub struct Pooled {
+    pub kind: PoolKind,
+    pub state: PoolState,
+}
+
+impl Pooled {
+    pub fn is_active(&self) -> bool {
+        self.state != PoolState::Free
+    }
+}
+
+// Spawners take entities from here instead of spawning new ones every time,
+// so the entity count stays at what was on screen at once
+#[derive(Default)]
+pub struct EntityPool {
+    free: HashMap<PoolKind, Vec<Entity>>,
+    releasing: Vec<Entity>,
+}
+
+impl EntityPool {
+    // Reuses a free entity of the kind, or spawns a new one.
+    // The components replace the ones the entity had, add the rest with commands.insert
+    pub fn spawn(
+        &mut self,
+        commands: &mut Commands,
+        kind: PoolKind,
+        components: impl DynamicBundle + Send + Sync + 'static,
+    ) -> Entity {
+        let entity = match self.free.get_mut(&kind).and_then(|free| free.pop()) {
+            Some(entity) => {
+                commands.insert(entity, components);
+                entity
+            }
+            None => commands.spawn(components).current_entity().unwrap(),
+        };
+        commands.insert_one(
+            entity,
+            Pooled {
+                kind,
+                state: PoolState::Taken,
+            },
+        );
+        entity
+    }
+
+    // Gives the entity back at the end of the tick. Releasing twice is fine,
+    // entities that aren't pooled are left alone
+    pub fn release(&mut self, entity: Entity) {
+        self.releasing.push(entity);
+    }
+
+    // Entities of all kinds waiting to be reused
+    pub fn free_count(&self) -> usize {
+        self.free.values().map(|free| free.len()).sum()
+    }
+}
+
+// One material per texture and tint, instead of a new one for every sprite
+#[derive(Default)]
+pub struct SharedMaterials {
+    materials: Vec<(Handle<Texture>, Color, Handle<ColorMaterial>)>,
+}
+
+impl SharedMaterials {
+    pub fn get(
+        &mut self,
+        materials: &mut Assets<ColorMaterial>,
+        texture: Handle<Texture>,
+        color: Color,
+    ) -> Handle<ColorMaterial> {
+        for (shared_texture, shared_color, material) in self.materials.iter() {
+            if *shared_texture == texture && *shared_color == color {
+                return *material;
+            }
+        }
+        let material = materials.add(ColorMaterial::modulated_texture(texture, color));
+        self.materials.push((texture, color, material));
+        material
+    }
+}
+
+pub struct PoolPlugin;
+
+impl Plugin for PoolPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<EntityPool>()
+            .init_resource::<SharedMaterials>()
+            .add_fixed_system_to_stage(FIXED_POST_UPDATE, pool_system.system());
+    }
+}
+
+// Runs in the fixed update, so a released score zone is gone before the next collision check
+fn pool_system(
+    mut pool: ResMut<EntityPool>,
+    mut query: Query<(
+        Entity,
+        &mut Pooled,
+        &mut Translation,
+        Option<&mut Velocity>,
+        Option<&mut Draw>,
+        Option<&mut Interpolated>,
+    )>,
+) {
+    for (entity, mut pooled, mut translation, velocity, draw, interpolated) in &mut query.iter() {
+        match pooled.state {
+            // Released on the tick it was taken, like pipes spawned on the tick of a death,
+            // goes straight back without ever being active
+            PoolState::Taken | PoolState::Active if pool.releasing.contains(&entity) => {
+                pooled.state = PoolState::Free;
+                translation.0 = Vec3::new(PARKED_POSITION, PARKED_POSITION, 0.0);
+                if let Some(mut velocity) = velocity {
+                    velocity.0 = Vec2::zero();
+                }
+                if let Some(mut draw) = draw {
+                    draw.is_visible = false;
+                }
+                if let Some(mut interpolated) = interpolated {
+                    *interpolated = Interpolated::new(translation.0);
+                }
+                pool.free.entry(pooled.kind).or_default().push(entity);
+            }
+            // Don't draw it moving over from where it was parked
+            PoolState::Taken => {
+                pooled.state = PoolState::Active;
+                if let Some(mut interpolated) = interpolated {
+                    *interpolated = Interpolated::new(translation.0);
+                }
+            }
+            PoolState::Active | PoolState::Free => {}
+        }
+    }
+    pool.releasing.clear();
+}
@@ -0,0 +1,208 @@
+use crate::bird;
+use crate::gamestate;
+use crate::physics;
+use crate::rng;
+use bevy::prelude::*;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+use bird::*;
+use gamestate::*;
+use physics::*;
+use rng::*;
+
+// Every finished run is saved here
+pub const LAST_REPLAY_PATH: &str = "replays/last.ron";
+// The highest scoring run so far
+pub const BEST_REPLAY_PATH: &str = "replays/best.ron";
+
+// Everything needed to play a run again: the seed for the pipes, and when the bird jumped
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    // Fixed ticks the bird jumped on, counted from the start of the run
+    pub jumps: Vec<u64>,
+    pub score: i32,
+    pub death_tick: u64,
+}
+
+impl Replay {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Replay, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read replay {}: {}", path.display(), err))?;
+        ron::de::from_str(&text)
+            .map_err(|err| format!("could not parse replay {}: {}", path.display(), err))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("could not create {}: {}", dir.display(), err))?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| format!("could not serialize replay: {}", err))?;
+        fs::write(path, text)
+            .map_err(|err| format!("could not write replay {}: {}", path.display(), err))
+    }
+}
+
+pub struct ReplayState {
+    // Set when playing a replay back, instead of recording one
+    playback: Option<Replay>,
+    start_tick: u64,
+    next_jump: usize,
+    recorded_jumps: Vec<u64>,
+    best: Option<Replay>,
+    // The run that ended last, played back ones too
+    last: Option<Replay>,
+    // Off for tests, so they don't overwrite the player's replays
+    pub save: bool,
+}
+
+impl ReplayState {
+    pub fn best(&self) -> Option<&Replay> {
+        self.best.as_ref()
+    }
+
+    pub fn last(&self) -> Option<&Replay> {
+        self.last.as_ref()
+    }
+}
+
+pub struct ReplayPlugin {
+    pub playback: Option<Replay>,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(ReplayState {
+            playback: self.playback.clone(),
+            start_tick: 0,
+            next_jump: 0,
+            recorded_jumps: Vec::new(),
+            // No best run yet is fine
+            best: Replay::load(BEST_REPLAY_PATH).ok(),
+            last: None,
+            save: true,
+        })
+        .add_system_to_stage(RUN_START, replay_run_start_system.system())
+        .add_fixed_system_to_stage(FIXED_PRE_UPDATE, replay_playback_system.system())
+        .add_system(replay_record_system.system());
+    }
+}
+
+fn replay_run_start_system(
+    mut run_event_reader: Local<EventReader<RunEvent>>,
+    run_events: Res<Events<RunEvent>>,
+    fixed_timestep: Res<FixedTimestep>,
+    mut replay_state: ResMut<ReplayState>,
+    mut rng: ResMut<GameRng>,
+) {
+    for run_event in run_event_reader.iter(&run_events) {
+        if let RunEvent::Started = run_event {
+            // Each run gets its own seed, so the run can be replayed on its own
+            let seed = match &replay_state.playback {
+                Some(replay) => replay.seed,
+                None => rng.next_u64(),
+            };
+            *rng = GameRng::from_seed(seed);
+            replay_state.start_tick = fixed_timestep.tick;
+            replay_state.next_jump = 0;
+            replay_state.recorded_jumps.clear();
+        }
+    }
+}
+
+// Feed the recorded jumps to the bird, on the same ticks they happened
+fn replay_playback_system(
+    state: Res<GameStateMachine>,
+    fixed_timestep: Res<FixedTimestep>,
+    mut replay_state: ResMut<ReplayState>,
+    mut jump_input: ResMut<JumpInput>,
+) {
+    if state.current() != GameState::Playing {
+        return;
+    }
+    let run_tick = fixed_timestep.tick - replay_state.start_tick;
+    let next_jump = replay_state.next_jump;
+    let jump_tick = match &replay_state.playback {
+        Some(replay) => replay.jumps.get(next_jump).copied(),
+        None => return,
+    };
+    if jump_tick == Some(run_tick) {
+        jump_input.requested = true;
+        replay_state.next_jump += 1;
+    }
+}
+
+fn replay_record_system(
+    mut jumped_event_reader: Local<EventReader<PlayerJumped>>,
+    mut run_event_reader: Local<EventReader<RunEvent>>,
+    jumped_events: Res<Events<PlayerJumped>>,
+    run_events: Res<Events<RunEvent>>,
+    rng: Res<GameRng>,
+    player_control: Res<PlayerControl>,
+    mut replay_state: ResMut<ReplayState>,
+) {
+    // Training and autopilot runs would keep overwriting the player's replays
+    if *player_control == PlayerControl::External || *player_control == PlayerControl::Autopilot {
+        return;
+    }
+    // Jumps first, the death may have happened in the same frame
+    for jumped in jumped_event_reader.iter(&jumped_events) {
+        let run_tick = jumped.tick - replay_state.start_tick;
+        replay_state.recorded_jumps.push(run_tick);
+    }
+    for run_event in run_event_reader.iter(&run_events) {
+        if let RunEvent::Ended { score, tick } = run_event {
+            let replay = Replay {
+                seed: rng.seed,
+                jumps: replay_state.recorded_jumps.clone(),
+                score: *score,
+                death_tick: *tick - replay_state.start_tick,
+            };
+            let expected = replay_state
+                .playback
+                .as_ref()
+                .map(|playback| (playback.score, playback.death_tick));
+            match expected {
+                Some((expected_score, expected_death_tick)) => {
+                    if expected_score == replay.score && expected_death_tick == replay.death_tick {
+                        println!(
+                            "replay matched: score {} at tick {}",
+                            replay.score, replay.death_tick
+                        );
+                    } else {
+                        println!(
+                            "replay diverged: expected score {} at tick {}, got score {} at tick {}",
+                            expected_score, expected_death_tick, replay.score, replay.death_tick
+                        );
+                    }
+                }
+                None => {
+                    if replay_state.save {
+                        if let Err(err) = replay.save(LAST_REPLAY_PATH) {
+                            println!("{}", err);
+                        }
+                    }
+                    let is_best = match &replay_state.best {
+                        Some(best) => replay.score > best.score,
+                        None => true,
+                    };
+                    if is_best {
+                        if replay_state.save {
+                            if let Err(err) = replay.save(BEST_REPLAY_PATH) {
+                                println!("{}", err);
+                            }
+                        }
+                        replay_state.best = Some(replay.clone());
+                    }
+                }
+            }
+            replay_state.last = Some(replay);
+        }
+    }
+}
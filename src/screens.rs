@@ -1,37 +1,102 @@
+use crate::gamestate;
+use crate::loading;
+use crate::playfield;
+use crate::tween;
 use bevy::prelude::*;
+use gamestate::*;
+use loading::*;
+use playfield::*;
+use tween::*;
 
 pub struct StartScreen;
 pub struct EndScreen;
 
+const SLIDE_IN_SECONDS: f32 = 0.8;
+const SLIDE_OUT_SECONDS: f32 = 0.4;
+
 pub struct ScreensPlugin;
 
 impl Plugin for ScreensPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(setup.system());
+        app.add_startup_system(setup.system())
+            .on_exit(GameState::Menu, hide_start_screen_system.system())
+            .on_enter(GameState::Dead, show_end_screen_system.system())
+            .on_exit(GameState::Dead, hide_end_screen_system.system());
+    }
+}
+
+// Screens wait above the playfield while they aren't shown
+fn offscreen(playfield: &Playfield) -> Vec3 {
+    Vec3::new(0.0, playfield.height, 0.0)
+}
+
+fn hide_start_screen_system(
+    mut commands: Commands,
+    playfield: Res<Playfield>,
+    mut query: Query<(&StartScreen, Entity, &Translation)>,
+) {
+    for (_start_screen, entity, translation) in &mut query.iter() {
+        let slide_out = TweenTarget::Translation {
+            from: translation.0,
+            to: offscreen(&playfield),
+        };
+        commands.insert_one(
+            entity,
+            Tween::new(slide_out, SLIDE_OUT_SECONDS, Ease::QuadIn),
+        );
+    }
+}
+
+fn show_end_screen_system(
+    mut commands: Commands,
+    playfield: Res<Playfield>,
+    mut query: Query<(&EndScreen, Entity)>,
+) {
+    for (_end_screen, entity) in &mut query.iter() {
+        let slide_in = TweenTarget::Translation {
+            from: offscreen(&playfield),
+            to: Vec3::zero(),
+        };
+        commands.insert_one(
+            entity,
+            Tween::new(slide_in, SLIDE_IN_SECONDS, Ease::BounceOut),
+        );
+    }
+}
+
+fn hide_end_screen_system(
+    mut commands: Commands,
+    playfield: Res<Playfield>,
+    mut query: Query<(&EndScreen, Entity, &Translation)>,
+) {
+    for (_end_screen, entity, translation) in &mut query.iter() {
+        let slide_out = TweenTarget::Translation {
+            from: translation.0,
+            to: offscreen(&playfield),
+        };
+        commands.insert_one(
+            entity,
+            Tween::new(slide_out, SLIDE_OUT_SECONDS, Ease::QuadIn),
+        );
     }
 }
 
 fn setup(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    game_assets: Res<GameAssets>,
+    playfield: Res<Playfield>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    let start_texture_handle = asset_server.load("assets/SpaceToStart.png").unwrap();
-    let game_over_texture_handle = asset_server.load("assets/GameOverText.png").unwrap();
     commands
         // Start Screen
         .spawn(SpriteComponents {
-            material: materials.add(start_texture_handle.into()),
+            material: materials.add(game_assets.start_screen_texture.into()),
             ..Default::default()
         })
         .with(StartScreen)
         .spawn(SpriteComponents {
-            material: materials.add(game_over_texture_handle.into()),
-            draw: Draw {
-                is_transparent: true,
-                is_visible: false,
-                render_commands: Vec::new(),
-            },
+            material: materials.add(game_assets.game_over_texture.into()),
+            translation: Translation(offscreen(&playfield)),
             ..Default::default()
         })
         .with(EndScreen);
@@ -0,0 +1,87 @@
+use crate::physics;
+use bevy::{app::stage, prelude::*};
+use std::time::Duration;
+
+use physics::*;
+
+pub const TIME_CONTROL: &str = "time_control";
+
+// Speeds to pick from with F7 and F8
+const TIME_SCALES: [f32; 6] = [0.1, 0.25, 0.5, 1.0, 2.0, 4.0];
+const NORMAL_SPEED: usize = 3;
+
+// Developer controls for the simulation speed, only in the windowed game.
+// F5 pauses, F6 runs a single fixed tick while paused, F7 and F8 slow down and speed up
+pub struct TimeControl {
+    pub paused: bool,
+    scale_index: usize,
+    step_requested: bool,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        TimeControl {
+            paused: false,
+            scale_index: NORMAL_SPEED,
+            step_requested: false,
+        }
+    }
+}
+
+impl TimeControl {
+    pub fn scale(&self) -> f32 {
+        TIME_SCALES[self.scale_index]
+    }
+}
+
+pub struct TimeControlPlugin;
+
+impl Plugin for TimeControlPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<TimeControl>()
+            .add_stage_after(stage::FIRST, TIME_CONTROL)
+            .add_system_to_stage(TIME_CONTROL, time_control_input_system.system())
+            .add_system_to_stage(TIME_CONTROL, scale_time_system.system());
+    }
+}
+
+fn time_control_input_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut time_control: ResMut<TimeControl>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        time_control.paused = !time_control.paused;
+    }
+    if keyboard_input.just_pressed(KeyCode::F6) && time_control.paused {
+        time_control.step_requested = true;
+    }
+    let scale_index = time_control.scale_index;
+    if keyboard_input.just_pressed(KeyCode::F7) && scale_index > 0 {
+        time_control.scale_index -= 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::F8) && scale_index + 1 < TIME_SCALES.len() {
+        time_control.scale_index += 1;
+    }
+    if time_control.scale_index != scale_index {
+        println!("time scale: {}x", time_control.scale());
+    }
+}
+
+// Everything reading Time after this sees the scaled delta:
+// the fixed update with all spawn timers, and the animation timers
+fn scale_time_system(
+    fixed_timestep: Res<FixedTimestep>,
+    mut time_control: ResMut<TimeControl>,
+    mut time: ResMut<Time>,
+) {
+    let delta_seconds = match (time_control.paused, time_control.step_requested) {
+        (false, _) => time.delta_seconds * time_control.scale(),
+        // Exactly one fixed tick worth of time
+        (true, true) => fixed_timestep.step,
+        (true, false) => 0.0,
+    };
+    time_control.step_requested = false;
+    time.delta = Duration::from_secs_f32(delta_seconds);
+    time.delta_seconds = delta_seconds;
+    time.delta_seconds_f64 = delta_seconds as f64;
+}
@@ -0,0 +1,215 @@
+use crate::bird;
+use crate::loading;
+use crate::parallax;
+use crate::physics;
+use crate::pipes;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path, time::SystemTime};
+
+use bird::*;
+use loading::*;
+use parallax::*;
+use physics::*;
+use pipes::*;
+
+pub const TUNING_PATH: &str = "assets/tuning.ron";
+// How often the tuning and parallax files are checked for changes
+const WATCH_SECONDS: f32 = 0.5;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PipeTuning {
+    pub min_time: f32,
+    pub max_time: f32,
+    pub speed: f32,
+    pub min_pipe_distance: f32,
+    pub max_pipe_distance: f32,
+    pub max_center_delta: f32,
+}
+
+// Numbers that decide how the game plays, loaded from TUNING_PATH
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tuning {
+    pub jump_height: f32,
+    pub gravity: f32,
+    pub pipes: PipeTuning,
+}
+
+// Compiled in from the shipped file, used when TUNING_PATH is missing or broken
+const DEFAULT_TUNING: &str = include_str!("../assets/tuning.ron");
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning::parse(DEFAULT_TUNING, "built in").unwrap()
+    }
+}
+
+impl Tuning {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Tuning, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read tuning {}: {}", path.display(), err))?;
+        Tuning::parse(&text, &path.display().to_string())
+    }
+
+    fn parse(text: &str, name: &str) -> Result<Tuning, String> {
+        let tuning: Tuning = ron::de::from_str(text)
+            .map_err(|err| format!("could not parse tuning {}: {}", name, err))?;
+        tuning
+            .validate()
+            .map_err(|err| format!("invalid tuning {}: {}", name, err))?;
+        Ok(tuning)
+    }
+
+    // Catch values that would break the game, instead of panicking later in gen_range
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if self.jump_height <= 0.0 {
+            errors.push("jump_height must be above 0");
+        }
+        if self.gravity <= 0.0 {
+            errors.push("gravity must be above 0");
+        }
+        if self.pipes.min_time <= 0.0 || self.pipes.min_time >= self.pipes.max_time {
+            errors.push("pipes need 0 < min_time < max_time");
+        }
+        if self.pipes.speed >= 0.0 {
+            errors.push("pipes.speed must be below 0, pipes move to the left");
+        }
+        if self.pipes.min_pipe_distance <= 0.0
+            || self.pipes.min_pipe_distance >= self.pipes.max_pipe_distance
+        {
+            errors.push("pipes need 0 < min_pipe_distance < max_pipe_distance");
+        }
+        if self.pipes.max_center_delta <= 0.0 {
+            errors.push("pipes.max_center_delta must be above 0");
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join(", ")),
+        }
+    }
+
+    fn pipe_spawn_settings(&self) -> PipeSpawnSettings {
+        PipeSpawnSettings {
+            min_time: self.pipes.min_time,
+            max_time: self.pipes.max_time,
+            speed: self.pipes.speed,
+            min_pipe_distance: self.pipes.min_pipe_distance,
+            max_pipe_distance: self.pipes.max_pipe_distance,
+            max_center_delta: self.pipes.max_center_delta,
+        }
+    }
+}
+
+// Loads the tuning file and adds the resources made from it.
+// A missing or broken file falls back to the defaults
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let tuning = match Tuning::load(TUNING_PATH) {
+            Ok(tuning) => tuning,
+            Err(err) => {
+                println!("{}, using default tuning", err);
+                Tuning::default()
+            }
+        };
+        app.add_resource(JumpHeight(tuning.jump_height))
+            .add_resource(Gravity(tuning.gravity))
+            .add_resource(tuning.pipe_spawn_settings())
+            .add_resource(tuning);
+    }
+}
+
+struct TuningWatch {
+    timer: Timer,
+    modified: Option<SystemTime>,
+    parallax_modified: Option<SystemTime>,
+}
+
+// Reloads the tuning and parallax files while the game runs, when they change on disk.
+// Only for the windowed game, simulations should stay reproducible
+pub struct TuningWatchPlugin;
+
+impl Plugin for TuningWatchPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(TuningWatch {
+            timer: Timer::from_seconds(WATCH_SECONDS, true),
+            modified: modified_time(TUNING_PATH),
+            parallax_modified: modified_time(PARALLAX_PATH),
+        })
+        .add_system(tuning_watch_system.system());
+    }
+}
+
+fn modified_time<P: AsRef<Path>>(path: P) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Whether the file was saved since the last check
+fn changed(path: &str, last_modified: &mut Option<SystemTime>) -> bool {
+    let modified = modified_time(path);
+    if modified.is_none() || modified == *last_modified {
+        return false;
+    }
+    *last_modified = modified;
+    true
+}
+
+fn tuning_watch_system(
+    time: Res<Time>,
+    game_assets: Res<GameAssets>,
+    mut watch: ResMut<TuningWatch>,
+    mut tuning: ResMut<Tuning>,
+    mut jump_height: ResMut<JumpHeight>,
+    mut gravity: ResMut<Gravity>,
+    mut pipe_spawn_settings: ResMut<PipeSpawnSettings>,
+    mut parallax_config: ResMut<ParallaxConfig>,
+    mut parallax_timers: ResMut<ParallaxTimers>,
+) {
+    watch.timer.tick(time.delta_seconds);
+    if !watch.timer.finished {
+        return;
+    }
+    // Keep playing with the old values until a broken file is fixed
+    if changed(TUNING_PATH, &mut watch.modified) {
+        match Tuning::load(TUNING_PATH) {
+            Ok(new_tuning) => {
+                jump_height.0 = new_tuning.jump_height;
+                gravity.0 = new_tuning.gravity;
+                *pipe_spawn_settings = new_tuning.pipe_spawn_settings();
+                *tuning = new_tuning;
+                println!("reloaded tuning {}", TUNING_PATH);
+            }
+            Err(err) => println!("{}, keeping the current tuning", err),
+        }
+    }
+    if changed(PARALLAX_PATH, &mut watch.parallax_modified) {
+        let reloaded = ParallaxConfig::load(PARALLAX_PATH).and_then(|new_config| {
+            replace_parallax(
+                &mut parallax_config,
+                &mut parallax_timers,
+                &game_assets,
+                new_config,
+            )
+        });
+        match reloaded {
+            Ok(()) => println!("reloaded parallax {}", PARALLAX_PATH),
+            Err(err) => println!("{}, keeping the current parallax", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_tuning_is_valid() {
+        let tuning = Tuning::default();
+        assert!(tuning.validate().is_ok());
+    }
+}
@@ -0,0 +1,237 @@
+use bevy::prelude::*;
+
+// Maps progress 0 -> 1 to eased progress, see easings.net for the curves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ease {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    // Overshoots a bit before settling
+    BackOut,
+    BounceOut,
+}
+
+impl Ease {
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Ease::Linear => t,
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Ease::QuadInOut => match t < 0.5 {
+                true => 2.0 * t * t,
+                false => 1.0 - (-2.0 * t + 2.0).powi(2) * 0.5,
+            },
+            Ease::BackOut => {
+                let overshoot = 1.70158;
+                let t = t - 1.0;
+                1.0 + (overshoot + 1.0) * t * t * t + overshoot * t * t
+            }
+            Ease::BounceOut => {
+                let n = 7.5625;
+                let d = 2.75;
+                if t < 1.0 / d {
+                    n * t * t
+                } else if t < 2.0 / d {
+                    let t = t - 1.5 / d;
+                    n * t * t + 0.75
+                } else if t < 2.5 / d {
+                    let t = t - 2.25 / d;
+                    n * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d;
+                    n * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+// What a tween changes, from one value to another
+#[derive(Clone, Copy, Debug)]
+pub enum TweenTarget {
+    Translation { from: Vec3, to: Vec3 },
+    Scale { from: f32, to: f32 },
+    // Angle around z in radians
+    Rotation { from: f32, to: f32 },
+    // Changes the material, so don't share it with other sprites
+    Color { from: Color, to: Color },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Repeat {
+    Never,
+    // Plays the whole sequence this many more times
+    Times(u32),
+    Forever,
+}
+
+struct TweenStep {
+    target: TweenTarget,
+    duration: f32,
+    ease: Ease,
+    // Seconds to wait before the step starts
+    delay: f32,
+}
+
+// A sequence of steps played one after another on the entity
+pub struct Tween {
+    steps: Vec<TweenStep>,
+    repeat: Repeat,
+    step: usize,
+    elapsed: f32,
+    finished: bool,
+}
+
+impl Tween {
+    pub fn new(target: TweenTarget, duration: f32, ease: Ease) -> Tween {
+        Tween {
+            steps: vec![TweenStep {
+                target,
+                duration,
+                ease,
+                delay: 0.0,
+            }],
+            repeat: Repeat::Never,
+            step: 0,
+            elapsed: 0.0,
+            finished: false,
+        }
+    }
+
+    // Delays the last added step
+    pub fn with_delay(mut self, delay: f32) -> Tween {
+        if let Some(step) = self.steps.last_mut() {
+            step.delay = delay;
+        }
+        self
+    }
+
+    // Plays another step once the previous one is done
+    pub fn then(mut self, target: TweenTarget, duration: f32, ease: Ease) -> Tween {
+        self.steps.push(TweenStep {
+            target,
+            duration,
+            ease,
+            delay: 0.0,
+        });
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: Repeat) -> Tween {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Seconds for all steps once
+    fn length(&self) -> f32 {
+        self.steps
+            .iter()
+            .map(|step| step.delay + step.duration)
+            .sum()
+    }
+}
+
+// Sent when a tween played all its steps and repeats
+pub struct TweenFinished {
+    pub entity: Entity,
+}
+
+pub struct TweenPlugin;
+
+impl Plugin for TweenPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<TweenFinished>()
+            .add_system(tween_system.system());
+    }
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    Color::rgba(
+        from.r + (to.r - from.r) * t,
+        from.g + (to.g - from.g) * t,
+        from.b + (to.b - from.b) * t,
+        from.a + (to.a - from.a) * t,
+    )
+}
+
+fn tween_system(
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut finished_events: ResMut<Events<TweenFinished>>,
+    mut query: Query<(
+        Entity,
+        &mut Tween,
+        Option<&mut Translation>,
+        Option<&mut Scale>,
+        Option<&mut Rotation>,
+        Option<&Handle<ColorMaterial>>,
+    )>,
+) {
+    for (entity, mut tween, mut translation, mut scale, mut rotation, material) in &mut query.iter()
+    {
+        if tween.finished {
+            continue;
+        }
+        tween.elapsed += time.delta_seconds;
+        loop {
+            let step = &tween.steps[tween.step];
+            let step_time = step.delay + step.duration;
+            let progress = match step.duration > 0.0 {
+                true => ((tween.elapsed - step.delay) / step.duration)
+                    .max(0.0)
+                    .min(1.0),
+                false => 1.0,
+            };
+            let t = step.ease.apply(progress);
+            match step.target {
+                TweenTarget::Translation { from, to } => {
+                    if let Some(translation) = translation.as_mut() {
+                        translation.0 = from.lerp(to, t);
+                    }
+                }
+                TweenTarget::Scale { from, to } => {
+                    if let Some(scale) = scale.as_mut() {
+                        scale.0 = from + (to - from) * t;
+                    }
+                }
+                TweenTarget::Rotation { from, to } => {
+                    if let Some(rotation) = rotation.as_mut() {
+                        rotation.0 = Quat::from_rotation_z(from + (to - from) * t);
+                    }
+                }
+                TweenTarget::Color { from, to } => {
+                    if let Some(material) = material.and_then(|handle| materials.get_mut(handle)) {
+                        material.color = lerp_color(from, to, t);
+                    }
+                }
+            }
+            // A long frame can finish several steps
+            if tween.elapsed < step_time {
+                break;
+            }
+            tween.elapsed -= step_time;
+            tween.step += 1;
+            if tween.step < tween.steps.len() {
+                continue;
+            }
+            tween.step = 0;
+            match tween.repeat {
+                // Nothing would ever take time, go on next frame
+                Repeat::Forever if tween.length() <= 0.0 => break,
+                Repeat::Forever => {}
+                Repeat::Times(times) if times > 0 => tween.repeat = Repeat::Times(times - 1),
+                Repeat::Times(_) | Repeat::Never => {
+                    tween.step = tween.steps.len() - 1;
+                    tween.finished = true;
+                    finished_events.send(TweenFinished { entity });
+                    break;
+                }
+            }
+        }
+    }
+}